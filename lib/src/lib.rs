@@ -2,10 +2,24 @@
 pub mod private {
     //! Modules that are intended to be used only inside
     //! `disko` crates
+    pub mod color;
+    pub mod diagnostics;
     pub mod eyre;
+    pub mod redact;
     pub mod tracing;
 }
 
+pub use private::color::ColorChoice;
+pub use private::diagnostics::DiagnosticsBuilder;
+pub use private::tracing::{
+    LevelGlyphs,
+    LoggingGuard,
+    MemoryWriter,
+    Overflow,
+    TimeFormat,
+    TimeZone
+};
+
 pub fn add(
     left: u64,
     right: u64
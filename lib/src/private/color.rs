@@ -0,0 +1,144 @@
+//! ## Color
+//!
+//! Single switch deciding whether output of `disko` crates
+//! is colored. Both log and panic report consult it, as
+//! well as everything styled with [`console`]
+
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{
+    AtomicU8,
+    Ordering
+};
+
+use console::Term;
+
+/// When to color output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Always color, even when piped
+    Always,
+    /// Color only terminals, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    /// Never color
+    Never
+}
+
+impl FromStr for ColorChoice {
+    type Err = color_eyre::Report;
+
+    /// Parse value of `--color` option
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "always" => Ok(Self::Always),
+            "auto" => Ok(Self::Auto),
+            "never" => Ok(Self::Never),
+            _ => Err(color_eyre::eyre::eyre!(
+                "Unknown color choice `{s}`, expected one of: always, auto, never"
+            ))
+        }
+    }
+}
+
+/// Stream output is written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// Log below `WARN`
+    Stdout,
+    /// Warnings, errors and panic reports
+    Stderr
+}
+
+static CHOICE: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Decision of [`ColorChoice::Auto`] per stream, made once
+/// since neither environment nor the streams change
+static AUTO: [OnceLock<bool>; 2] = [OnceLock::new(), OnceLock::new()];
+
+/// Whether to color `stream` when nothing is forced
+fn auto(stream: Stream) -> bool {
+    *AUTO[stream as usize].get_or_init(|| {
+        let term = match stream {
+            Stream::Stdout => Term::stdout(),
+            Stream::Stderr => Term::stderr()
+        };
+        auto_decision(std::env::var_os("NO_COLOR").as_deref(), term.is_term())
+    })
+}
+
+/// `NO_COLOR` disables colors whenever it's set and not
+/// empty, as <https://no-color.org> says
+fn auto_decision(
+    no_color: Option<&std::ffi::OsStr>,
+    term: bool
+) -> bool {
+    term && no_color.is_none_or(|v| v.is_empty())
+}
+
+/// Override color decision for the whole process
+pub fn set_color_choice(choice: ColorChoice) {
+    CHOICE.store(choice as u8, Ordering::Relaxed);
+    sync_console();
+}
+
+/// Make [`console`] styling follow the same decision
+pub(crate) fn sync_console() {
+    console::set_colors_enabled(should_colorize(Stream::Stdout));
+    console::set_colors_enabled_stderr(should_colorize(Stream::Stderr));
+}
+
+/// Current color choice
+pub fn color_choice() -> ColorChoice {
+    match CHOICE.load(Ordering::Relaxed) {
+        x if x == ColorChoice::Always as u8 => ColorChoice::Always,
+        x if x == ColorChoice::Never as u8 => ColorChoice::Never,
+        _ => ColorChoice::Auto
+    }
+}
+
+/// Whether output written to `stream` should be colored
+pub fn should_colorize(stream: Stream) -> bool {
+    match color_choice() {
+        ColorChoice::Always => true,
+        ColorChoice::Auto => auto(stream),
+        ColorChoice::Never => false
+    }
+}
+
+/// Whether output written to `stream` should be colored
+/// if it were piped, i.e. only when colors are forced.
+/// Used for sinks that are never terminals
+pub(crate) fn should_colorize_piped(_stream: Stream) -> bool {
+    color_choice() == ColorChoice::Always
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use super::{
+        ColorChoice,
+        auto_decision
+    };
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            "always".parse::<ColorChoice>().unwrap(),
+            ColorChoice::Always
+        );
+        assert_eq!("auto".parse::<ColorChoice>().unwrap(), ColorChoice::Auto);
+        assert_eq!("never".parse::<ColorChoice>().unwrap(), ColorChoice::Never);
+        assert!("sometimes".parse::<ColorChoice>().is_err());
+    }
+
+    #[test]
+    fn auto() {
+        assert!(auto_decision(None, true));
+        assert!(!auto_decision(None, false));
+        assert!(!auto_decision(Some(OsStr::new("1")), true));
+        // Empty value doesn't count
+        assert!(auto_decision(Some(OsStr::new("")), true));
+    }
+}
@@ -0,0 +1,386 @@
+//! ## Diagnostics
+//!
+//! Module provides single entrypoint configuring both log
+//! and error reports
+
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+use crate::private::color::{
+    ColorChoice,
+    set_color_choice
+};
+use crate::private::eyre::PanicReport;
+use crate::private::tracing::{
+    LevelGlyphs,
+    LogOptions,
+    LoggingGuard,
+    Overflow,
+    TimeFormat,
+    TimeZone
+};
+
+/// Configures log, error and panic hooks, installing all
+/// of them together with [`install`](Self::install)
+///
+/// ```no_run
+/// use disko_lib::{
+///     ColorChoice,
+///     DiagnosticsBuilder
+/// };
+///
+/// let _guard = DiagnosticsBuilder::new()
+///     .with_filter("warn,disko_lib=debug")
+///     .with_file("/var/log/disko.log")
+///     .colors(ColorChoice::Never)
+///     .install()?;
+/// # color_eyre::Result::<()>::Ok(())
+/// ```
+#[must_use = "nothing is installed until `install` is called"]
+pub struct DiagnosticsBuilder {
+    /// Log configuration
+    log:    LogOptions,
+    /// Panic report configuration
+    report: PanicReport,
+    /// Color choice to apply, if any
+    colors: Option<ColorChoice>
+}
+
+impl DiagnosticsBuilder {
+    /// Builder with the defaults: log filtered by
+    /// `RUST_LOG` to terminal, crashdumps saved to
+    /// temporary directory unless
+    /// [`NO_CRASHDUMP_ENV`](crate::private::eyre::NO_CRASHDUMP_ENV)
+    /// is set
+    pub fn new() -> Self {
+        Self {
+            log:    LogOptions::default(),
+            report: PanicReport {
+                persist: crate::private::eyre::persist_from_env(),
+                ..PanicReport::default()
+            },
+            colors: None
+        }
+    }
+
+    /// Show only what `directives` allow, in `RUST_LOG`
+    /// syntax (e.g. `warn,disko_lib=debug`), instead of
+    /// what `RUST_LOG` says
+    pub fn with_filter(
+        mut self,
+        directives: &str
+    ) -> Self {
+        self.log.filter = Some(directives.into());
+        self
+    }
+
+    /// Show more with positive `verbosity` and less with
+    /// negative one, e.g. count of `-v` minus count of
+    /// `-q`. `0` shows `INFO` and above, `1` and `2` add
+    /// `DEBUG` and `TRACE`, `-1` and `-2` leave only `WARN`
+    /// and `ERROR`, `-3` and below silence log. `RUST_LOG`
+    /// wins when it's set,
+    /// [`with_filter`](Self::with_filter)
+    /// wins over both
+    pub fn verbosity(
+        mut self,
+        verbosity: i8
+    ) -> Self {
+        self.log.verbosity = verbosity;
+        self
+    }
+
+    /// Also write every event to `path` without colors,
+    /// creating missing parent directories
+    pub fn with_file(
+        mut self,
+        path: impl Into<PathBuf>
+    ) -> Self {
+        self.log.file = Some(path.into());
+        self
+    }
+
+    /// Write log to `writer`, e.g. a
+    /// [`MemoryWriter`](crate::MemoryWriter),
+    /// instead of the terminal. Lines are formatted as
+    /// piped stdout would be, colored only with
+    /// [`ColorChoice::Always`]
+    pub fn with_writer(
+        mut self,
+        writer: impl for<'w> MakeWriter<'w> + Send + Sync + 'static
+    ) -> Self {
+        self.log.writer = Some(BoxMakeWriter::new(writer));
+        self
+    }
+
+    /// Write one JSON object per event to stdout instead of
+    /// formatted lines
+    pub fn json(
+        mut self,
+        json: bool
+    ) -> Self {
+        self.log.json = json;
+        self
+    }
+
+    /// Send every event to journald, failing on install
+    /// when it's not running. Log is still written to
+    /// stdout when it's a terminal, e.g. when run by hand
+    /// rather than by a systemd unit
+    #[cfg(feature = "journald")]
+    pub fn journald(mut self) -> Self {
+        self.log.journald = true;
+        self
+    }
+
+    /// Timestamp format of log lines
+    pub fn time_format(
+        mut self,
+        format: TimeFormat
+    ) -> Self {
+        self.log.time = format;
+        self
+    }
+
+    /// Zone of wall-clock timestamps
+    pub fn time_zone(
+        mut self,
+        zone: TimeZone
+    ) -> Self {
+        self.log.time_zone = zone;
+        self
+    }
+
+    /// Handling of lines wider than the terminal
+    pub fn overflow(
+        mut self,
+        overflow: Overflow
+    ) -> Self {
+        self.log.overflow = overflow;
+        self
+    }
+
+    /// Show how long the event's spans have been running,
+    /// e.g. `mount(…) [412ms]`
+    pub fn span_timing(
+        mut self,
+        span_timing: bool
+    ) -> Self {
+        self.log.span_timing = span_timing;
+        self
+    }
+
+    /// Never write log lines wider than `max_width` columns
+    pub fn max_line_width(
+        mut self,
+        max_width: usize
+    ) -> Self {
+        self.log.max_width = Some(max_width);
+        self
+    }
+
+    /// Mark each level with one of `glyphs`, e.g. `⚠  WARN`
+    pub fn level_glyphs(
+        mut self,
+        glyphs: LevelGlyphs
+    ) -> Self {
+        self.log.glyphs = Some(glyphs);
+        self
+    }
+
+    /// Turn source locations into OSC 8 hyperlinks to `url`
+    /// on terminals with colors, e.g.
+    /// [`FILE_URL`](crate::private::tracing::FILE_URL) or
+    /// `vscode://file/{path}:{line}`. `{path}` is replaced
+    /// with absolute path of the file, `{line}` with the
+    /// line number. Only enable it for terminals supporting
+    /// such links
+    pub fn hyperlinks(
+        mut self,
+        url: &str
+    ) -> Self {
+        self.log.hyperlinks = Some(url.into());
+        self
+    }
+
+    /// Keep scope and source location within `max_width`
+    /// columns. Span field values are shortened first, then
+    /// span names, then the scope itself
+    pub fn scope_width(
+        mut self,
+        max_width: usize
+    ) -> Self {
+        self.log.scope_width = Some(max_width);
+        self
+    }
+
+    /// Start scope of messages shorter than `column` at
+    /// that column instead of the right edge, so it
+    /// stays in place from line to line, e.g.
+    /// [`ANCHOR_COLUMN`](crate::private::tracing::ANCHOR_COLUMN)
+    pub fn anchor_column(
+        mut self,
+        column: usize
+    ) -> Self {
+        self.log.anchor = Some(column);
+        self
+    }
+
+    /// Show values of event and span fields whose names
+    /// contain one of `keys` (ignoring case) as `****` in
+    /// every sink, in addition to
+    /// [`REDACTED_FIELDS`](crate::private::redact::REDACTED_FIELDS).
+    /// Crash context hides them too once installed
+    pub fn redacted_fields(
+        mut self,
+        keys: &[&str]
+    ) -> Self {
+        self.log.redacted.extend(keys.iter().map(|&key| key.into()));
+        self
+    }
+
+    /// Override color decision for log and reports
+    pub fn colors(
+        mut self,
+        choice: ColorChoice
+    ) -> Self {
+        self.colors = Some(choice);
+        self
+    }
+
+    /// Name of the application shown in panic reports and
+    /// crashdumps
+    pub fn app_name(
+        mut self,
+        app_name: &str
+    ) -> Self {
+        self.report.app_name = app_name.into();
+        self
+    }
+
+    /// Where panic reports ask users to report the crash
+    pub fn issue_url(
+        mut self,
+        issue_url: &str
+    ) -> Self {
+        self.report.issue_url = issue_url.into();
+        self
+    }
+
+    /// Whether to save crashdumps
+    pub fn crashdump(
+        mut self,
+        persist: bool
+    ) -> Self {
+        self.report.persist = persist;
+        self
+    }
+
+    /// Save crashdumps to `dir` instead of the temporary
+    /// directory
+    pub fn crashdump_dir(
+        mut self,
+        dir: impl Into<PathBuf>
+    ) -> Self {
+        self.report.dump_dir = Some(dir.into());
+        self
+    }
+
+    /// Install error and panic hooks, then trace
+    /// dispatcher. Keep returned guard alive until exit.
+    /// Parts installed already stay in place. Nothing is
+    /// installed when log configuration is invalid
+    pub fn install(self) -> Result<LoggingGuard> {
+        let Self {
+            log,
+            report,
+            colors
+        } = self;
+        let log = crate::private::tracing::prepare(log)?;
+        if let Some(choice) = colors {
+            set_color_choice(choice);
+        }
+        crate::private::eyre::install_report(report)?;
+        Ok(log.install())
+    }
+
+    /// Install only the trace dispatcher
+    pub(crate) fn install_log(self) -> Result<LoggingGuard> {
+        let log = crate::private::tracing::prepare(self.log)?;
+        if let Some(choice) = self.colors {
+            set_color_choice(choice);
+        }
+        Ok(log.install())
+    }
+
+    /// Install only error and panic hooks
+    pub(crate) fn install_hooks(self) -> Result<()> {
+        if let Some(choice) = self.colors {
+            set_color_choice(choice);
+        }
+        crate::private::eyre::install_report(self.report)
+    }
+}
+
+impl Default for DiagnosticsBuilder {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiagnosticsBuilder;
+    use crate::private::color::{
+        ColorChoice,
+        color_choice
+    };
+
+    #[test]
+    fn invalid_filter() {
+        let err = DiagnosticsBuilder::new()
+            .crashdump(false)
+            .colors(ColorChoice::Never)
+            .with_filter("disko_lib=loud")
+            .install()
+            .unwrap_err();
+        assert!(err.to_string().contains("Invalid log filter"), "{err}");
+        // Failed before touching global state
+        assert_eq!(color_choice(), ColorChoice::Auto);
+    }
+
+    #[test]
+    fn unwritable_file() {
+        let file = std::env::temp_dir().join(format!("disko-builder-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        // Not opened at all once log is installed, e.g. by
+        // other tests
+        let builder = DiagnosticsBuilder::new().with_file(file.join("disko.log"));
+        let err = crate::private::tracing::build(builder.log).err().unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert!(
+            err.to_string().contains("Failed to create log directory"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn redacted_fields_extend_defaults() {
+        let builder = DiagnosticsBuilder::new()
+            .redacted_fields(&["pin"])
+            .redacted_fields(&["otp"]);
+        assert_eq!(builder.log.redacted, ["pin", "otp"]);
+    }
+
+    #[test]
+    fn install_twice() {
+        let builder = || {
+            DiagnosticsBuilder::new()
+                .crashdump(false)
+                .with_filter("off")
+        };
+        drop(builder().install().unwrap());
+        drop(builder().json(true).install().unwrap());
+    }
+}
@@ -3,76 +3,327 @@
 //! Module provides human-friendly error messages and panic
 //! reports with [`color_eyre`] and [`human_panic`] crates
 
+use std::path::PathBuf;
+
+use crate::private::diagnostics::DiagnosticsBuilder;
+
 /// Issue URL where user should submut an issue
-const ISSUE_URL: &str = "https://github.com/nix-community/disko";
+const ISSUE_URL: &str = concat!(env!("CARGO_PKG_REPOSITORY"), "/issues");
+
+/// Name of the application shown in panic report
+const APP_NAME: &str = "disko";
+
+/// Environment variable disabling crashdump persistence
+/// when set to a non-empty value
+pub const NO_CRASHDUMP_ENV: &str = "DISKO_NO_CRASHDUMP";
+
+mod context {
+    //! ## Crash context
+    //! Key/value info about what disko is doing right now,
+    //! saved into crashdump if it panics
+
+    use std::collections::BTreeMap;
+    use std::sync::{
+        Mutex,
+        PoisonError,
+        TryLockError
+    };
+
+    use crate::private::redact::Secrets;
+
+    /// Value stored instead of a secret
+    const REDACTED: &str = "<redacted>";
+
+    static CONTEXT: Mutex<BTreeMap<String, String>> = Mutex::new(BTreeMap::new());
+
+    /// Register `value` under `key`, replacing the previous
+    /// one. Values of keys
+    /// [`REDACTED_FIELDS`](crate::private::redact::REDACTED_FIELDS)
+    /// name are never stored
+    pub fn set_context(
+        key: impl Into<String>,
+        value: impl ToString
+    ) {
+        let key = key.into();
+        let value = if Secrets::installed().contains(&key) {
+            REDACTED.into()
+        } else {
+            value.to_string()
+        };
+        CONTEXT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, value);
+    }
+
+    /// Forget context registered under `key`
+    pub fn remove_context(key: &str) {
+        CONTEXT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+    }
+
+    /// Copy of registered context. Doesn't wait for the
+    /// lock, so panicking while holding it can't
+    /// deadlock the hook
+    pub(super) fn snapshot() -> BTreeMap<String, String> {
+        match CONTEXT.try_lock() {
+            Ok(context) => context.clone(),
+            Err(TryLockError::Poisoned(e)) => e.into_inner().clone(),
+            Err(TryLockError::WouldBlock) => BTreeMap::new()
+        }
+    }
+}
+
+pub use context::{
+    remove_context,
+    set_context
+};
 
 mod panic {
     //! ## Panic
     //! Human-friendly colorful panic report with crashdump.
-    //! Heavily inspired by [`human_panic`]
+    //! Heavily inspired by [`human_panic`]. Backtrace goes
+    //! only to the crashdump, screen gets a summary
+
+    use std::any::Any;
+    use std::backtrace::Backtrace;
+    use std::borrow::Cow;
+    use std::ffi::OsStr;
+    use std::fmt::{
+        Result,
+        Write
+    };
+    use std::path::{
+        Path,
+        PathBuf
+    };
 
-    use color_eyre::section::PanicMessage;
     use console::strip_ansi_codes;
+    use human_panic::report::Method;
     use owo_colors::OwoColorize as _;
 
-    use super::ISSUE_URL;
+    use super::{
+        APP_NAME,
+        ISSUE_URL
+    };
+    use crate::private::color::{
+        Stream,
+        should_colorize
+    };
+
+    /// Show cursor, reset attributes and move to a fresh
+    /// line, in case a progress bar or prompt was drawing
+    /// when the panic happened. Harmless when nothing was.
+    /// Alternate screen is left alone: leaving it restores
+    /// saved cursor even when it wasn't entered
+    const TERMINAL_RESET: &str = "\x1b[?25h\x1b[0m\r\n";
+
+    /// Message carried by a panic payload. Besides strings
+    /// this understands a few types commonly passed to
+    /// [`std::panic::panic_any`]
+    fn payload_message(payload: &(dyn Any + Send)) -> Cow<'_, str> {
+        macro_rules! display {
+            ($($ty:ty),*) => {
+                $(if let Some(v) = payload.downcast_ref::<$ty>() {
+                    return v.to_string().into();
+                })*
+            };
+        }
+
+        if let Some(s) = payload.downcast_ref::<&str>() {
+            return (*s).into();
+        }
+        if let Some(s) = payload.downcast_ref::<String>() {
+            return s.as_str().into();
+        }
+        display!(
+            Box<str>,
+            Cow<'static, str>,
+            color_eyre::Report,
+            std::io::Error,
+            i32,
+            i64,
+            u32,
+            u64,
+            usize
+        );
+        "<non-string payload>".into()
+    }
+
+    /// Number inside `ThreadId(n)`, as [`ThreadId`] has no
+    /// stable way to get it. Whole debug form is kept if it
+    /// ever looks different
+    ///
+    /// [`ThreadId`]: std::thread::ThreadId
+    fn thread_id(id: std::thread::ThreadId) -> String {
+        let debug = format!("{id:?}");
+        debug
+            .strip_prefix("ThreadId(")
+            .and_then(|id| id.strip_suffix(')'))
+            .map_or_else(|| debug.clone(), str::to_owned)
+    }
+
+    /// Whether `RUST_BACKTRACE` set to `value` asks for a
+    /// backtrace, decided like the default panic hook does
+    pub(super) fn wants_backtrace(value: Option<&OsStr>) -> bool { value.is_some_and(|v| v != "0") }
+
+    /// What crashed and where
+    struct Crash<'a> {
+        /// Panic message
+        message:   &'a str,
+        /// Thread name and id
+        thread:    &'a str,
+        /// Source location, possibly colored
+        location:  &'a str,
+        /// Captured only when `RUST_BACKTRACE` asks for it,
+        /// goes to crashdump only
+        backtrace: Option<&'a str>
+    }
 
     /// Type representing panic message
-    pub(super) struct PanicReport;
+    pub(crate) struct PanicReport {
+        /// Whether crashdump should be saved to disk
+        pub(crate) persist:   bool,
+        /// Application that crashed
+        pub(crate) app_name:  String,
+        /// Where users should report the crash
+        pub(crate) issue_url: String,
+        /// Directory crashdump is saved to, temporary
+        /// directory if [`None`]
+        pub(crate) dump_dir:  Option<PathBuf>
+    }
+
+    impl Default for PanicReport {
+        fn default() -> Self {
+            Self {
+                persist:   true,
+                app_name:  APP_NAME.into(),
+                issue_url: ISSUE_URL.into(),
+                dump_dir:  None
+            }
+        }
+    }
+
+    impl PanicReport {
+        /// Save `crash` together with registered context
+        /// into `dir`, named like
+        /// [`Report::persist`](human_panic::report::Report::persist)
+        /// does. Missing `dir` is created
+        fn persist(
+            &self,
+            crash: &Crash<'_>,
+            dir: &Path
+        ) -> color_eyre::Result<PathBuf> {
+            let explanation = format!(
+                "Panic occurred in file {}",
+                strip_ansi_codes(crash.location)
+            );
+            let mut dump = toml::Table::new();
+            dump.insert("name".into(), self.app_name.as_str().into());
+            dump.insert("operating_system".into(), os_info::get().to_string().into());
+            dump.insert("crate_version".into(), env!("CARGO_PKG_VERSION").into());
+            dump.insert("explanation".into(), explanation.into());
+            dump.insert("cause".into(), crash.message.into());
+            dump.insert("method".into(), toml::Value::try_from(Method::Panic)?);
+            if let Some(backtrace) = crash.backtrace {
+                dump.insert("backtrace".into(), backtrace.into());
+            }
+            let context = super::context::snapshot();
+            if !context.is_empty() {
+                let context = context
+                    .into_iter()
+                    .map(|(k, v)| (k, toml::Value::String(v)))
+                    .collect();
+                dump.insert("context".into(), toml::Value::Table(context));
+            }
 
-    impl PanicMessage for PanicReport {
-        fn display(
+            std::fs::create_dir_all(dir)?;
+            let uuid = uuid::Uuid::new_v4().hyphenated();
+            let path = dir.join(format!("report-{uuid}.toml"));
+            std::fs::write(&path, toml::to_string(&dump)?)?;
+            Ok(path)
+        }
+
+        /// Write the whole report. Terminal is restored
+        /// first, but only if `terminal` is set, so piped
+        /// output stays free of escape sequences. Colors
+        /// are stripped unless `colorize` is set
+        fn write_report(
             &self,
-            pi: &std::panic::PanicHookInfo<'_>,
-            f: &mut std::fmt::Formatter<'_>
-        ) -> std::fmt::Result {
+            f: &mut impl Write,
+            terminal: bool,
+            colorize: bool,
+            crash: &Crash<'_>
+        ) -> Result {
+            if terminal {
+                f.write_str(TERMINAL_RESET)?;
+            }
+
+            let mut report = String::new();
             writeln!(
-                f,
-                "\nDisko had unrecoverable error and {}.",
+                report,
+                "\n{} had unrecoverable error and {}.",
+                self.app_name,
                 "crashed".red().bold(),
             )?;
-            writeln!(f, "Here's some info about error:")?;
-
-            let message = pi.payload_as_str().unwrap_or("<not string>");
-            writeln!(f, "    {}:  {}", "Message".red().bold(), message.blue())?;
-
-            let thread = std::thread::current();
-            let thread_name = thread.name().unwrap_or("<no name>");
-            let thread_id = unsafe { std::mem::transmute::<_, u64>(thread.id()) };
+            writeln!(report, "Here's some info about error:")?;
             writeln!(
-                f,
-                "    {}:   {} (id: {})",
-                "Thread".red().bold(),
-                thread_name.yellow(),
-                thread_id.yellow()
+                report,
+                "    {}:  {}",
+                "Message".red().bold(),
+                crash.message.blue()
             )?;
+            writeln!(report, "    {}:   {}", "Thread".red().bold(), crash.thread)?;
+            writeln!(
+                report,
+                "    {}: {}",
+                "Location".red().bold(),
+                crash.location
+            )?;
+            self.write_footer(&mut report, crash)?;
 
-            let location = if let Some(loc) = pi.location() {
-                format!(
-                    "{}, line {}, column {}",
-                    loc.file().purple(),
-                    loc.line().purple(),
-                    loc.column().purple()
-                )
+            if colorize {
+                f.write_str(&report)
             } else {
-                "???".into()
-            };
-            writeln!(f, "    {}: {}", "Location".red().bold(), location)?;
-
-            let report = human_panic::report::Report::new(
-                env!("CARGO_PKG_VERSION"),
-                env!("CARGO_PKG_VERSION"),
-                human_panic::report::Method::Panic,
-                format!("Panic occurred in file {}", strip_ansi_codes(&location)),
-                message.into()
-            );
-            let dump = report.persist();
+                f.write_str(&strip_ansi_codes(&report))
+            }
+        }
+
+        /// Write crashdump location, or just where to
+        /// report the crash when persistence is
+        /// disabled
+        fn write_footer(
+            &self,
+            f: &mut impl Write,
+            crash: &Crash<'_>
+        ) -> Result {
+            if !self.persist {
+                writeln!(f, "\nSaving crashdump is disabled.")?;
+                writeln!(
+                    f,
+                    "Please, submit an issue at {} and include the info above.",
+                    self.issue_url.blue()
+                )?;
+                return Ok(());
+            }
+
+            let dir = self.dump_dir.clone().unwrap_or_else(std::env::temp_dir);
+            let dump = self.persist(crash, &dir);
             if let Ok(path) = dump {
                 writeln!(f, "\nMore info saved at {}.", path.display().blue())?;
+                if crash.backtrace.is_none() {
+                    writeln!(
+                        f,
+                        "Run with {} to include a backtrace.",
+                        "RUST_BACKTRACE=1".yellow()
+                    )?;
+                }
                 writeln!(
                     f,
                     "Please, submit an issue at {} and attach report.",
-                    ISSUE_URL.blue()
+                    self.issue_url.blue()
                 )?;
             } else {
                 writeln!(
@@ -80,18 +331,407 @@ mod panic {
                     "\nTried to safe crashdump but failed: {}",
                     dump.unwrap_err()
                 )?;
-                writeln!(f, "Please, submit an issue at {}.", ISSUE_URL.blue())?;
+                writeln!(f, "Please, submit an issue at {}.", self.issue_url.blue())?;
             }
 
             Ok(())
         }
+
+        /// Print report of `pi` to stderr, saving
+        /// crashdump if enabled. Backtrace is captured only
+        /// when `RUST_BACKTRACE` asks for it, and never
+        /// printed
+        pub(crate) fn report(
+            &self,
+            pi: &std::panic::PanicHookInfo<'_>
+        ) {
+            use std::io::Write as _;
+
+            let message = payload_message(pi.payload());
+
+            let thread = std::thread::current();
+            let thread_name = thread.name().unwrap_or("<no name>");
+            let thread_id = thread_id(thread.id());
+            let thread = format!("{} (id: {})", thread_name.yellow(), thread_id.yellow());
+
+            let location = if let Some(loc) = pi.location() {
+                format!(
+                    "{}, line {}, column {}",
+                    loc.file().purple(),
+                    loc.line().purple(),
+                    loc.column().purple()
+                )
+            } else {
+                "???".into()
+            };
+
+            let backtrace = wants_backtrace(std::env::var_os("RUST_BACKTRACE").as_deref())
+                .then(|| Backtrace::force_capture().to_string());
+
+            let terminal = console::Term::stderr().is_term();
+            let colorize = should_colorize(Stream::Stderr);
+            let mut out = String::new();
+            let crash = Crash {
+                message:   &message,
+                thread:    &thread,
+                location:  &location,
+                backtrace: backtrace.as_deref()
+            };
+            let written = self.write_report(&mut out, terminal, colorize, &crash);
+            if written.is_ok() {
+                // Nowhere to report failures to
+                let _ = std::io::stderr().lock().write_all(out.as_bytes());
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::collections::BTreeSet;
+        use std::path::PathBuf;
+
+        use console::strip_ansi_codes;
+
+        use super::{
+            Crash,
+            PanicReport,
+            TERMINAL_RESET
+        };
+        use crate::private::eyre::set_context;
+
+        /// Crash without backtrace
+        const CRASH: Crash<'static> = Crash {
+            message:   "boom",
+            thread:    "main (id: 1)",
+            location:  "src/main.rs, line 1, column 1",
+            backtrace: None
+        };
+
+        /// Render report without saving crashdump
+        fn report(
+            terminal: bool,
+            colorize: bool
+        ) -> String {
+            let mut out = String::new();
+            PanicReport {
+                persist: false,
+                ..PanicReport::default()
+            }
+            .write_report(&mut out, terminal, colorize, &CRASH)
+            .unwrap();
+            out
+        }
+
+        /// Crashdumps currently in the temporary directory
+        fn dumps() -> BTreeSet<PathBuf> {
+            std::fs::read_dir(std::env::temp_dir())
+                .unwrap()
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| {
+                    p.file_name()
+                        .and_then(|n| n.to_str())
+                        .is_some_and(|n| n.starts_with("report-") && n.ends_with(".toml"))
+                })
+                .collect()
+        }
+
+        #[test]
+        fn payload_messages() {
+            let message = |payload: Box<dyn std::any::Any + Send>| {
+                super::payload_message(payload.as_ref()).into_owned()
+            };
+            assert_eq!(message(Box::new("static")), "static");
+            assert_eq!(message(Box::new(format!("formatted {}", 1))), "formatted 1");
+            assert_eq!(message(Box::new(Box::<str>::from("boxed"))), "boxed");
+            assert_eq!(
+                message(Box::new(color_eyre::eyre::eyre!("no such device"))),
+                "no such device"
+            );
+            assert_eq!(message(Box::new(42_i32)), "42");
+            assert!(message(Box::new(())).starts_with("<non-string payload"));
+
+            // Real panic payload
+            let payload = std::panic::catch_unwind(|| panic!("{} failed", "mount")).unwrap_err();
+            assert_eq!(message(payload), "mount failed");
+        }
+
+        #[test]
+        fn thread_id_is_number() {
+            let id = super::thread_id(std::thread::current().id());
+            assert!(id.parse::<u64>().is_ok(), "{id}");
+        }
+
+        #[test]
+        fn no_persist() {
+            let before = dumps();
+            let mut out = String::new();
+            PanicReport {
+                persist: false,
+                ..PanicReport::default()
+            }
+            .write_footer(&mut out, &CRASH)
+            .unwrap();
+
+            let out = strip_ansi_codes(&out);
+            assert!(out.contains("Saving crashdump is disabled."), "{out}");
+            assert!(!out.contains("More info saved at"), "{out}");
+            assert_eq!(before, dumps());
+        }
+
+        #[test]
+        fn context_in_dump() {
+            set_context("device", "/dev/nvme0n1");
+            set_context("phase", "format");
+            set_context("luksPassphrase", "hunter2");
+
+            let dir = std::env::temp_dir().join(format!("disko-test-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            let path = PanicReport::default().persist(&CRASH, &dir).unwrap();
+            let dump = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+
+            assert!(dump.contains("[context]"), "{dump}");
+            assert!(dump.contains("device = \"/dev/nvme0n1\""), "{dump}");
+            assert!(dump.contains("phase = \"format\""), "{dump}");
+            assert!(dump.contains("luksPassphrase = \"<redacted>\""), "{dump}");
+            assert!(!dump.contains("hunter2"), "{dump}");
+        }
+
+        #[test]
+        fn terminal_reset_comes_first() {
+            let out = report(true, true);
+            assert!(out.starts_with(TERMINAL_RESET), "{out:?}");
+            // Would move the cursor when no UI was active
+            assert!(!out.contains("\x1b[?1049l"), "{out:?}");
+            assert!(strip_ansi_codes(&out).contains("disko had unrecoverable error"));
+        }
+
+        #[test]
+        fn configured_app_and_url() {
+            let mut out = String::new();
+            PanicReport {
+                persist: false,
+                app_name: "acme-installer".into(),
+                issue_url: "https://example.com/acme/issues".into(),
+                ..PanicReport::default()
+            }
+            .write_report(&mut out, false, false, &CRASH)
+            .unwrap();
+
+            assert!(
+                out.starts_with("\nacme-installer had unrecoverable error"),
+                "{out}"
+            );
+            assert!(out.contains("https://example.com/acme/issues"), "{out}");
+        }
+
+        #[test]
+        fn dump_dir() {
+            let dir = std::env::temp_dir()
+                .join(format!("disko-dumps-{}", std::process::id()))
+                .join("nested");
+            let mut out = String::new();
+            PanicReport {
+                app_name: "acme-installer".into(),
+                dump_dir: Some(dir.clone()),
+                ..PanicReport::default()
+            }
+            .write_footer(&mut out, &CRASH)
+            .unwrap();
+
+            let dumps = std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .collect::<Vec<_>>();
+            std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+
+            assert_eq!(dumps.len(), 1, "{dumps:?}");
+            let dump = &dumps[0];
+            assert!(
+                strip_ansi_codes(&out).contains(&dump.display().to_string()),
+                "{out}"
+            );
+        }
+
+        #[test]
+        fn dump_names_app() {
+            let dir = std::env::temp_dir().join(format!("disko-dump-name-{}", std::process::id()));
+            let path = PanicReport {
+                app_name: "acme-installer".into(),
+                ..PanicReport::default()
+            }
+            .persist(&CRASH, &dir)
+            .unwrap();
+            let dump = std::fs::read_to_string(&path).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+
+            let dump: toml::Table = dump.parse().unwrap();
+            assert_eq!(dump["name"].as_str(), Some("acme-installer"));
+            assert_eq!(dump["method"].as_str(), Some("Panic"));
+            assert_eq!(dump["cause"].as_str(), Some("boom"));
+            assert_eq!(
+                dump["explanation"].as_str(),
+                Some("Panic occurred in file src/main.rs, line 1, column 1")
+            );
+            assert!(dump.contains_key("operating_system"), "{dump:?}");
+            assert!(!dump.contains_key("backtrace"), "{dump:?}");
+        }
+
+        #[test]
+        fn wants_backtrace() {
+            use std::ffi::OsStr;
+
+            assert!(!super::wants_backtrace(None));
+            assert!(!super::wants_backtrace(Some(OsStr::new("0"))));
+            assert!(super::wants_backtrace(Some(OsStr::new("1"))));
+            assert!(super::wants_backtrace(Some(OsStr::new("full"))));
+        }
+
+        #[test]
+        fn backtrace_off_screen() {
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            let mut out = String::new();
+            PanicReport {
+                persist: false,
+                ..PanicReport::default()
+            }
+            .write_report(
+                &mut out,
+                false,
+                false,
+                &Crash {
+                    backtrace: Some(&backtrace),
+                    ..CRASH
+                }
+            )
+            .unwrap();
+
+            assert!(backtrace.contains("backtrace_off_screen"), "{backtrace}");
+            assert!(!out.contains("backtrace_off_screen"), "{out}");
+            // No frames like `  0: disko::main`
+            assert!(
+                !out.lines().any(|l| l
+                    .trim_start()
+                    .split_once(": ")
+                    .is_some_and(|(n, _)| n.parse::<u32>().is_ok())),
+                "{out}"
+            );
+        }
+
+        #[test]
+        fn backtrace_in_dump() {
+            let dir = std::env::temp_dir().join(format!("disko-backtrace-{}", std::process::id()));
+            let report = |backtrace| {
+                let mut out = String::new();
+                PanicReport {
+                    dump_dir: Some(dir.clone()),
+                    ..PanicReport::default()
+                }
+                .write_footer(&mut out, &Crash { backtrace, ..CRASH })
+                .unwrap();
+                let out = strip_ansi_codes(&out).into_owned();
+                let path = out
+                    .split_once("More info saved at ")
+                    .and_then(|(_, rest)| rest.split_once(".\n"))
+                    .map(|(path, _)| PathBuf::from(path))
+                    .unwrap();
+                (out, std::fs::read_to_string(path).unwrap())
+            };
+
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            let (out, dump) = report(Some(&backtrace));
+            let (hint, plain) = report(None);
+            std::fs::remove_dir_all(&dir).unwrap();
+
+            // On screen just a summary
+            assert!(!out.contains("backtrace_in_dump"), "{out}");
+            assert!(!out.contains("RUST_BACKTRACE"), "{out}");
+            let dump: toml::Table = dump.parse().unwrap();
+            assert_eq!(dump["backtrace"].as_str(), Some(backtrace.as_str()));
+            assert!(backtrace.contains("backtrace_in_dump"), "{backtrace}");
+
+            assert!(hint.contains("Run with RUST_BACKTRACE=1"), "{hint}");
+            let plain: toml::Table = plain.parse().unwrap();
+            assert!(!plain.contains_key("backtrace"), "{plain:?}");
+        }
+
+        #[test]
+        fn no_color_is_plain() {
+            let out = report(false, false);
+            assert!(!out.contains('\x1b'), "{out:?}");
+            assert!(out.contains("Message:  boom"), "{out:?}");
+        }
+
+        #[test]
+        fn no_reset_without_terminal() {
+            let out = report(false, true);
+            assert!(!out.contains(TERMINAL_RESET), "{out:?}");
+            assert!(
+                out.starts_with("\ndisko had unrecoverable error"),
+                "{out:?}"
+            );
+        }
     }
 }
 
-/// Install error and panic hooks
-pub fn install() -> color_eyre::Result<()> {
-    color_eyre::config::HookBuilder::blank()
-        .panic_message(panic::PanicReport)
+pub(crate) use panic::PanicReport;
+
+/// Install error and panic hooks, saving crashdumps unless
+/// [`NO_CRASHDUMP_ENV`] is set
+pub fn install() -> color_eyre::Result<()> { DiagnosticsBuilder::new().install_hooks() }
+
+/// Whether crashdumps should be saved, according to
+/// [`NO_CRASHDUMP_ENV`]
+pub(crate) fn persist_from_env() -> bool {
+    std::env::var_os(NO_CRASHDUMP_ENV).is_none_or(|v| v.is_empty())
+}
+
+/// Install error and panic hooks, saving crashdumps only if
+/// `persist` is set
+pub fn install_with_crashdump(persist: bool) -> color_eyre::Result<()> {
+    DiagnosticsBuilder::new().crashdump(persist).install_hooks()
+}
+
+/// Install error and panic hooks reporting crashes of
+/// `app_name` to `issue_url`, e.g. for downstreams
+/// embedding `disko`. Crashdumps go to `dump_dir`, or to
+/// the temporary directory if it's [`None`]
+pub fn install_with(
+    app_name: &str,
+    issue_url: &str,
+    dump_dir: Option<PathBuf>
+) -> color_eyre::Result<()> {
+    let builder = DiagnosticsBuilder::new()
+        .app_name(app_name)
+        .issue_url(issue_url);
+    match dump_dir {
+        Some(dir) => builder.crashdump_dir(dir),
+        None => builder
+    }
+    .install_hooks()
+}
+
+/// Install hooks, doing nothing if some were installed
+/// already
+pub(crate) fn install_report(report: PanicReport) -> color_eyre::Result<()> {
+    crate::private::color::sync_console();
+    // Panic hook is ours, the one of `color_eyre` prints
+    // backtraces on screen
+    let (_, eyre_hook) = color_eyre::config::HookBuilder::blank()
         .display_env_section(false)
-        .install()
+        .try_into_hooks()?;
+    // Earlier hooks, panic hook included, stay in place
+    if eyre_hook.install().is_ok() {
+        std::panic::set_hook(Box::new(move |pi| report.report(pi)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn install_twice() {
+        super::install_with_crashdump(false).unwrap();
+        super::install_with_crashdump(false).unwrap();
+    }
 }
@@ -0,0 +1,106 @@
+//! ## Redaction
+//!
+//! Single rule deciding which keys hold secrets. Log and
+//! crashdump both consult it, so a value hidden in one is
+//! never shown by the other
+
+use std::sync::RwLock;
+
+/// Keys always holding secrets
+pub const REDACTED_FIELDS: &[&str] = &["passphrase", "password", "key", "secret", "token"];
+
+/// Keys added by the installed log, so the crash context
+/// hides them too
+static INSTALLED: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Keys whose values are never shown
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Secrets(Vec<String>);
+
+impl Secrets {
+    /// Secrets named by `keys`
+    pub(crate) fn new(keys: &[impl AsRef<str>]) -> Self {
+        Self(keys.iter().map(|key| key.as_ref().to_lowercase()).collect())
+    }
+
+    /// [`REDACTED_FIELDS`] and `keys`
+    pub(crate) fn with(keys: &[impl AsRef<str>]) -> Self {
+        let mut secrets = Self::default();
+        secrets.extend(keys);
+        secrets
+    }
+
+    /// Secrets of the installed log, or [`REDACTED_FIELDS`]
+    /// before one is installed
+    pub(crate) fn installed() -> Self {
+        let keys = INSTALLED.read().unwrap_or_else(|e| e.into_inner());
+        Self::with(&keys)
+    }
+
+    /// Make these keys secret for the whole process
+    pub(crate) fn register(&self) {
+        let mut keys = INSTALLED.write().unwrap_or_else(|e| e.into_inner());
+        for key in &self.0 {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+    }
+
+    /// Whether `key` contains one of the secret keys,
+    /// ignoring case, e.g. `luksPassphrase` or `key_file`
+    pub(crate) fn contains(
+        &self,
+        key: &str
+    ) -> bool {
+        let key = key.to_lowercase();
+        self.0.iter().any(|secret| key.contains(secret.as_str()))
+    }
+
+    fn extend(
+        &mut self,
+        keys: &[impl AsRef<str>]
+    ) {
+        for key in keys {
+            let key = key.as_ref().to_lowercase();
+            if !self.0.contains(&key) {
+                self.0.push(key);
+            }
+        }
+    }
+}
+
+impl Default for Secrets {
+    fn default() -> Self { Self::new(REDACTED_FIELDS) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Secrets;
+
+    #[test]
+    fn matching() {
+        let secrets = Secrets::default();
+        assert!(secrets.contains("passphrase"));
+        assert!(secrets.contains("luksPassphrase"));
+        assert!(secrets.contains("key_file"));
+        assert!(secrets.contains("API_TOKEN"));
+        assert!(!secrets.contains("device"));
+
+        let custom = Secrets::new(&["PIN"]);
+        assert!(custom.contains("sim_pin"));
+        assert!(!custom.contains("password"));
+
+        let extended = Secrets::with(&["PIN"]);
+        assert!(extended.contains("sim_pin"));
+        assert!(extended.contains("password"));
+    }
+
+    #[test]
+    fn registered() {
+        assert!(!Secrets::installed().contains("redact_test_otp"));
+        Secrets::with(&["redact_test_otp"]).register();
+        assert!(Secrets::installed().contains("redact_test_otp"));
+        assert!(Secrets::installed().contains("password"));
+    }
+}
@@ -2,48 +2,690 @@
 //!
 //! Module provides custom log format for [`tracing`]
 
+use std::path::{
+    Path,
+    PathBuf
+};
+use std::sync::{
+    Arc,
+    Mutex,
+    PoisonError
+};
+
 use color_eyre::Result;
 use color_eyre::eyre::Context as _;
+use tracing::level_filters::LevelFilter;
+use tracing::{
+    Dispatch,
+    Subscriber
+};
+use tracing_appender::non_blocking::{
+    NonBlocking,
+    WorkerGuard
+};
+use tracing_subscriber::filter::Filtered;
+use tracing_subscriber::fmt::writer::{
+    BoxMakeWriter,
+    MakeWriterExt as _
+};
+use tracing_subscriber::fmt::{
+    FormatEvent,
+    MakeWriter
+};
+use tracing_subscriber::layer::{
+    Layered,
+    SubscriberExt as _
+};
+use tracing_subscriber::{
+    EnvFilter,
+    Layer as _,
+    Registry
+};
+
+use crate::private::diagnostics::DiagnosticsBuilder;
+use crate::private::redact::Secrets;
 
 mod time {
     //! ## Time
     //!
     //! Implements time formatting in logs
 
-    use std::fmt::Result;
     use std::time::{
-        SystemTime,
-        UNIX_EPOCH
+        Duration,
+        Instant,
+        SystemTime
     };
 
+    use chrono::format::StrftimeItems;
+    use chrono::{
+        DateTime,
+        FixedOffset,
+        Local,
+        Offset as _,
+        Utc
+    };
+    use color_eyre::Result;
+    use color_eyre::eyre::eyre;
     use owo_colors::OwoColorize as _;
     use tracing_subscriber::fmt::format::Writer;
     use tracing_subscriber::fmt::time::FormatTime;
 
+    /// Timestamp style of log lines
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
+    pub enum TimeFormat {
+        /// Wall-clock time, `[HH:MM:SS.mmm]`
+        #[default]
+        Time,
+        /// Date and wall-clock time,
+        /// `[YYYY-MM-DD HH:MM:SS.mmm]`
+        DateTime,
+        /// Time since logging was installed,
+        /// `[+MM:SS.mmm]`
+        Relative,
+        /// No timestamp at all
+        None,
+        /// Custom [`chrono`] format string, rendered as is
+        Custom(String)
+    }
+
+    /// Time zone wall-clock timestamps are shown in
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum TimeZone {
+        /// System local time zone. Offset is looked up
+        /// once, so a DST change during the run
+        /// isn't reflected
+        #[default]
+        Local,
+        /// Coordinated universal time
+        Utc
+    }
+
+    impl TimeZone {
+        /// Current offset of the zone from UTC
+        fn offset(self) -> FixedOffset {
+            match self {
+                Self::Local => Local::now().offset().fix(),
+                Self::Utc => Utc.fix()
+            }
+        }
+    }
+
+    /// Split duration into hours, minutes, seconds and
+    /// milliseconds
+    fn hms(duration: Duration) -> (u64, u64, u64, u32) {
+        let secs = duration.as_secs();
+        (
+            secs / 3600,
+            secs % 3600 / 60,
+            secs % 60,
+            duration.subsec_millis()
+        )
+    }
+
+    /// Time elapsed since logging was installed
+    pub(super) struct RelativeTime {
+        start: Instant
+    }
+
+    impl RelativeTime {
+        pub(super) fn new() -> Self {
+            Self {
+                start: Instant::now()
+            }
+        }
+
+        /// Render `elapsed`, without colors
+        pub(super) fn render(elapsed: Duration) -> String {
+            match hms(elapsed) {
+                (0, mins, secs, millis) => format!("[+{mins:02}:{secs:02}.{millis:03}]"),
+                (hours, mins, secs, millis) => {
+                    format!("[+{hours}:{mins:02}:{secs:02}.{millis:03}]")
+                }
+            }
+        }
+    }
+
+    impl FormatTime for RelativeTime {
+        fn format_time(
+            &self,
+            w: &mut Writer<'_>
+        ) -> std::fmt::Result {
+            write!(w, "{} ", Self::render(self.start.elapsed()).blue().dimmed())
+        }
+    }
+
     /// A type representing time format in logs
-    pub(super) struct TimeFormatter;
+    pub(super) struct TimeFormatter {
+        format:   TimeFormat,
+        /// Offset of wall-clock timestamps, resolved once
+        offset:   FixedOffset,
+        /// Clock of [`TimeFormat::Relative`]
+        relative: RelativeTime
+    }
+
+    impl TimeFormatter {
+        /// Create formatter, checking custom format strings
+        pub(super) fn new(
+            format: TimeFormat,
+            zone: TimeZone
+        ) -> Result<Self> {
+            if let TimeFormat::Custom(custom) = &format {
+                StrftimeItems::new(custom)
+                    .parse()
+                    .map_err(|e| eyre!("Invalid time format {custom:?}: {e}"))?;
+            }
+
+            Ok(Self {
+                format,
+                offset: zone.offset(),
+                relative: RelativeTime::new()
+            })
+        }
+
+        /// Render timestamp of `now`, without colors
+        pub(super) fn render(
+            &self,
+            now: SystemTime
+        ) -> String {
+            let now = DateTime::<Utc>::from(now).with_timezone(&self.offset);
+            match &self.format {
+                TimeFormat::Time => format!("[{}]", now.format("%H:%M:%S%.3f")),
+                TimeFormat::DateTime => format!("[{}]", now.format("%Y-%m-%d %H:%M:%S%.3f")),
+                TimeFormat::Relative => RelativeTime::render(self.relative.start.elapsed()),
+                TimeFormat::None => String::new(),
+                TimeFormat::Custom(custom) => now.format(custom).to_string()
+            }
+        }
+    }
 
     impl FormatTime for TimeFormatter {
         fn format_time(
             &self,
             w: &mut Writer<'_>
+        ) -> std::fmt::Result {
+            if let TimeFormat::Relative = self.format {
+                return self.relative.format_time(w);
+            }
+            let time = self.render(SystemTime::now());
+            if time.is_empty() {
+                return Ok(());
+            }
+            write!(w, "{} ", time.blue().dimmed())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::time::UNIX_EPOCH;
+
+        use super::*;
+
+        /// 2023-11-14 22:14:05.123 UTC
+        fn instant() -> SystemTime { UNIX_EPOCH + Duration::from_millis(1_700_000_045_123) }
+
+        fn render(format: TimeFormat) -> String {
+            TimeFormatter::new(format, TimeZone::Utc)
+                .unwrap()
+                .render(instant())
+        }
+
+        #[test]
+        fn presets() {
+            assert_eq!(render(TimeFormat::Time), "[22:14:05.123]");
+            assert_eq!(render(TimeFormat::DateTime), "[2023-11-14 22:14:05.123]");
+            assert_eq!(render(TimeFormat::None), "");
+        }
+
+        #[test]
+        fn seconds_across_midnight() {
+            // 2023-11-14 23:59:59.999 UTC, a bitmask with
+            // 60 would turn 59 seconds into 56
+            let before = UNIX_EPOCH + Duration::from_millis(1_700_006_399_999);
+            let after = before + Duration::from_millis(1);
+
+            let time = TimeFormatter::new(TimeFormat::Time, TimeZone::Utc).unwrap();
+            assert_eq!(time.render(before), "[23:59:59.999]");
+            assert_eq!(time.render(after), "[00:00:00.000]");
+
+            let date = TimeFormatter::new(TimeFormat::DateTime, TimeZone::Utc).unwrap();
+            assert_eq!(date.render(before), "[2023-11-14 23:59:59.999]");
+            assert_eq!(date.render(after), "[2023-11-15 00:00:00.000]");
+        }
+
+        #[test]
+        fn custom() {
+            assert_eq!(
+                render(TimeFormat::Custom("%d/%m %H:%M".into())),
+                "14/11 22:14"
+            );
+            assert!(TimeFormatter::new(TimeFormat::Custom("%Q".into()), TimeZone::Utc).is_err());
+        }
+
+        #[test]
+        fn offset() {
+            let formatter = TimeFormatter {
+                offset: FixedOffset::east_opt(2 * 3600).unwrap(),
+                ..TimeFormatter::new(TimeFormat::DateTime, TimeZone::Utc).unwrap()
+            };
+            assert_eq!(formatter.render(instant()), "[2023-11-15 00:14:05.123]");
+
+            let local = TimeFormatter::new(TimeFormat::Custom("%:z".into()), TimeZone::Local)
+                .unwrap()
+                .render(instant());
+            assert_eq!(local, Local::now().offset().to_string());
+        }
+
+        #[test]
+        fn relative() {
+            let render = |ms| RelativeTime::render(Duration::from_millis(ms));
+            assert_eq!(render(3_412), "[+00:03.412]");
+            assert_eq!(render(754_001), "[+12:34.001]");
+            assert_eq!(render(3_723_000), "[+1:02:03.000]");
+
+            let formatter = TimeFormatter::new(TimeFormat::Relative, TimeZone::Local).unwrap();
+            assert!(formatter.render(instant()).starts_with("[+00:00."));
+
+            let mut line = String::new();
+            formatter.format_time(&mut Writer::new(&mut line)).unwrap();
+            assert!(line.contains("[+00:00."), "{line:?}");
+        }
+    }
+}
+
+mod fields {
+    //! ## Fields
+    //!
+    //! Captures span fields as typed values, so they can be
+    //! rendered without re-parsing formatted strings
+
+    use std::fmt::{
+        Debug,
+        Display,
+        Formatter,
+        Result
+    };
+    use std::time::{
+        Duration,
+        Instant
+    };
+
+    use owo_colors::OwoColorize as _;
+    use tracing::Subscriber;
+    use tracing::field::{
+        Field,
+        Visit
+    };
+    use tracing::span::{
+        Attributes,
+        Id,
+        Record
+    };
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::field::{
+        RecordFields,
+        VisitOutput as _
+    };
+    use tracing_subscriber::fmt::FormatFields;
+    use tracing_subscriber::fmt::format::{
+        DefaultVisitor,
+        Writer
+    };
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use crate::private::redact::Secrets;
+
+    /// A single span field value with its original type
+    /// kept
+    #[derive(Debug, Clone, PartialEq)]
+    pub(super) enum FieldValue {
+        Bool(bool),
+        Int(i128),
+        Uint(u128),
+        Float(f64),
+        Str(String),
+        /// Anything recorded only through [`Debug`]
+        Debug(String),
+        /// Value of a sensitive field, never shown
+        Redacted
+    }
+
+    impl FieldValue {
+        /// Render value colored according to its type
+        pub(super) fn colored(&self) -> String {
+            match self {
+                Self::Bool(b) => b.magenta().bold().to_string(),
+                Self::Int(i) => i.yellow().bold().to_string(),
+                Self::Uint(u) => u.yellow().bold().to_string(),
+                Self::Float(f) => f.yellow().bold().to_string(),
+                Self::Str(s) => s.cyan().bold().to_string(),
+                Self::Debug(d) => d.cyan().to_string(),
+                Self::Redacted => self.dimmed().to_string()
+            }
+        }
+    }
+
+    impl Display for FieldValue {
+        fn fmt(
+            &self,
+            f: &mut Formatter<'_>
         ) -> Result {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("SystemTime before UNIX EPOCH!");
-            let secs = now.as_secs() % (60 * 60 * 24);
-
-            let hours = secs / (60 * 60);
-            let mins = (secs % 3600) / 60;
-            let secs = secs & 60;
-            let millis = now.subsec_millis();
-            write!(
-                w,
-                "{}",
-                format!("[{hours:02}:{mins:02}:{secs:02}.{millis:03}]")
-                    .blue()
-                    .dimmed()
-            )
+            match self {
+                Self::Bool(b) => write!(f, "{b}"),
+                Self::Int(i) => write!(f, "{i}"),
+                Self::Uint(u) => write!(f, "{u}"),
+                Self::Float(fl) => write!(f, "{fl}"),
+                Self::Str(s) | Self::Debug(s) => write!(f, "{s}"),
+                Self::Redacted => write!(f, "****")
+            }
+        }
+    }
+
+    /// Render span duration compactly, e.g. `412ms`,
+    /// `3.05s` or `2m07s`
+    pub(super) fn elapsed(duration: Duration) -> String {
+        let millis = duration.as_millis();
+        match millis {
+            0..1_000 => format!("{millis}ms"),
+            1_000..60_000 => format!("{}.{:02}s", millis / 1_000, millis % 1_000 / 10),
+            _ => format!("{}m{:02}s", millis / 60_000, millis % 60_000 / 1_000)
+        }
+    }
+
+    /// Typed fields of a span, stored in span extensions
+    #[derive(Debug, Default)]
+    pub(super) struct SpanFields(pub(super) Vec<(&'static str, FieldValue)>);
+
+    impl SpanFields {
+        /// Hide values of `secrets`
+        pub(super) fn redact(
+            &mut self,
+            secrets: &Secrets
+        ) {
+            for (key, value) in &mut self.0 {
+                if secrets.contains(key) {
+                    *value = FieldValue::Redacted;
+                }
+            }
+        }
+
+        fn set(
+            &mut self,
+            key: &'static str,
+            value: FieldValue
+        ) {
+            match self.0.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, v)) => *v = value,
+                None => self.0.push((key, value))
+            }
+        }
+    }
+
+    impl Visit for SpanFields {
+        fn record_bool(
+            &mut self,
+            field: &Field,
+            value: bool
+        ) {
+            self.set(field.name(), FieldValue::Bool(value))
+        }
+
+        fn record_debug(
+            &mut self,
+            field: &Field,
+            value: &dyn Debug
+        ) {
+            self.set(field.name(), FieldValue::Debug(format!("{value:?}")))
+        }
+
+        fn record_f64(
+            &mut self,
+            field: &Field,
+            value: f64
+        ) {
+            self.set(field.name(), FieldValue::Float(value))
+        }
+
+        fn record_i128(
+            &mut self,
+            field: &Field,
+            value: i128
+        ) {
+            self.set(field.name(), FieldValue::Int(value))
+        }
+
+        fn record_i64(
+            &mut self,
+            field: &Field,
+            value: i64
+        ) {
+            self.set(field.name(), FieldValue::Int(value.into()))
+        }
+
+        fn record_str(
+            &mut self,
+            field: &Field,
+            value: &str
+        ) {
+            self.set(field.name(), FieldValue::Str(value.into()))
+        }
+
+        fn record_u128(
+            &mut self,
+            field: &Field,
+            value: u128
+        ) {
+            self.set(field.name(), FieldValue::Uint(value))
+        }
+
+        fn record_u64(
+            &mut self,
+            field: &Field,
+            value: u64
+        ) {
+            self.set(field.name(), FieldValue::Uint(value.into()))
+        }
+    }
+
+    /// When a span was first entered, stored in span
+    /// extensions
+    pub(super) struct SpanStart(pub(super) Instant);
+
+    /// Layer recording span fields into [`SpanFields`] and
+    /// their first entry into [`SpanStart`]
+    #[derive(Default)]
+    pub(super) struct FieldsLayer {
+        /// Fields whose values are redacted
+        secrets: Secrets
+    }
+
+    impl FieldsLayer {
+        /// Layer redacting `secrets`
+        pub(super) fn new(secrets: Secrets) -> Self { Self { secrets } }
+    }
+
+    impl<S> Layer<S> for FieldsLayer
+    where S: Subscriber + for<'a> LookupSpan<'a>
+    {
+        fn on_new_span(
+            &self,
+            attrs: &Attributes<'_>,
+            id: &Id,
+            ctx: Context<'_, S>
+        ) {
+            let Some(span) = ctx.span(id) else {
+                return;
+            };
+            let mut fields = SpanFields::default();
+            attrs.record(&mut fields);
+            fields.redact(&self.secrets);
+            span.extensions_mut().insert(fields);
+        }
+
+        fn on_record(
+            &self,
+            id: &Id,
+            values: &Record<'_>,
+            ctx: Context<'_, S>
+        ) {
+            let Some(span) = ctx.span(id) else {
+                return;
+            };
+            let mut ext = span.extensions_mut();
+            if let Some(fields) = ext.get_mut::<SpanFields>() {
+                values.record(fields);
+                fields.redact(&self.secrets);
+            }
+        }
+
+        fn on_enter(
+            &self,
+            id: &Id,
+            ctx: Context<'_, S>
+        ) {
+            let Some(span) = ctx.span(id) else {
+                return;
+            };
+            let mut ext = span.extensions_mut();
+            if ext.get_mut::<SpanStart>().is_none() {
+                ext.insert(SpanStart(Instant::now()));
+            }
+        }
+    }
+
+    /// Formats event and span fields like
+    /// [`DefaultFields`](tracing_subscriber::fmt::format::DefaultFields),
+    /// showing values of `secrets` as `****`
+    #[derive(Default)]
+    pub(super) struct RedactingFields(pub(super) Secrets);
+
+    impl<'w> FormatFields<'w> for RedactingFields {
+        fn format_fields<R: RecordFields>(
+            &self,
+            writer: Writer<'w>,
+            fields: R
+        ) -> Result {
+            let mut visitor = Redacting {
+                inner:   DefaultVisitor::new(writer, true),
+                secrets: &self.0
+            };
+            fields.record(&mut visitor);
+            visitor.inner.finish()
+        }
+    }
+
+    /// Visitor passing fields on to `inner`, secrets
+    /// replaced with [`FieldValue::Redacted`]
+    struct Redacting<'a, V> {
+        inner:   V,
+        secrets: &'a Secrets
+    }
+
+    impl<V: Visit> Redacting<'_, V> {
+        /// Whether `field` was redacted instead of passed
+        /// on
+        fn redacted(
+            &mut self,
+            field: &Field
+        ) -> bool {
+            let secret = self.secrets.contains(field.name());
+            if secret {
+                self.inner
+                    .record_debug(field, &format_args!("{}", FieldValue::Redacted));
+            }
+            secret
+        }
+    }
+
+    impl<V: Visit> Visit for Redacting<'_, V> {
+        fn record_bool(
+            &mut self,
+            field: &Field,
+            value: bool
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_bool(field, value)
+            }
+        }
+
+        fn record_debug(
+            &mut self,
+            field: &Field,
+            value: &dyn Debug
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_debug(field, value)
+            }
+        }
+
+        fn record_error(
+            &mut self,
+            field: &Field,
+            value: &(dyn std::error::Error + 'static)
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_error(field, value)
+            }
+        }
+
+        fn record_f64(
+            &mut self,
+            field: &Field,
+            value: f64
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_f64(field, value)
+            }
+        }
+
+        fn record_i128(
+            &mut self,
+            field: &Field,
+            value: i128
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_i128(field, value)
+            }
+        }
+
+        fn record_i64(
+            &mut self,
+            field: &Field,
+            value: i64
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_i64(field, value)
+            }
+        }
+
+        fn record_str(
+            &mut self,
+            field: &Field,
+            value: &str
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_str(field, value)
+            }
+        }
+
+        fn record_u128(
+            &mut self,
+            field: &Field,
+            value: u128
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_u128(field, value)
+            }
+        }
+
+        fn record_u64(
+            &mut self,
+            field: &Field,
+            value: u64
+        ) {
+            if !self.redacted(field) {
+                self.inner.record_u64(field, value)
+            }
         }
     }
 }
@@ -54,13 +696,25 @@ mod format {
     //! Format final log message
 
     use std::fmt::Result;
+    use std::path::Path;
+    use std::sync::atomic::{
+        AtomicBool,
+        AtomicUsize,
+        Ordering
+    };
+    use std::sync::{
+        Arc,
+        LazyLock
+    };
 
     use console::{
         Term,
         measure_text_width,
-        strip_ansi_codes
+        strip_ansi_codes,
+        truncate_str
     };
     use owo_colors::OwoColorize as _;
+    use signal_hook::consts::SIGWINCH;
     use tracing::{
         Event,
         Level,
@@ -76,7 +730,210 @@ mod format {
     };
     use tracing_subscriber::registry::LookupSpan;
 
-    pub(super) struct TracingFormatter;
+    use super::fields::{
+        SpanFields,
+        SpanStart,
+        elapsed
+    };
+    use super::time::{
+        TimeFormat,
+        TimeFormatter,
+        TimeZone
+    };
+    use crate::private::color::{
+        Stream,
+        should_colorize
+    };
+
+    /// Narrowest scope worth keeping on the message line
+    const MIN_SCOPE_WIDTH: usize = 24;
+
+    /// Narrowest message worth wrapping next to the scope
+    const MIN_MESSAGE_WIDTH: usize = 16;
+
+    /// Handling of log lines wider than the terminal
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Overflow {
+        /// Cut the end of the scope, or move it to the next
+        /// line when the message leaves no room for it
+        #[default]
+        Truncate,
+        /// Cut the scope between target and source
+        /// location, keeping the location whole
+        Elide,
+        /// Wrap the message onto indented continuation
+        /// lines, scope stays aligned on the first one
+        Wrap
+    }
+
+    /// Symbols shown before log level, one per level
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LevelGlyphs {
+        /// Shown before `TRACE`
+        pub trace: String,
+        /// Shown before `DEBUG`
+        pub debug: String,
+        /// Shown before `INFO`
+        pub info:  String,
+        /// Shown before `WARN`
+        pub warn:  String,
+        /// Shown before `ERROR`
+        pub error: String
+    }
+
+    impl LevelGlyphs {
+        /// Glyphs for terminals without good Unicode
+        /// support
+        pub fn ascii() -> Self {
+            Self {
+                trace: ".".into(),
+                debug: "*".into(),
+                info:  ">".into(),
+                warn:  "!".into(),
+                error: "x".into()
+            }
+        }
+
+        /// Glyph of `level`
+        fn get(
+            &self,
+            level: Level
+        ) -> &str {
+            match level {
+                Level::TRACE => &self.trace,
+                Level::DEBUG => &self.debug,
+                Level::INFO => &self.info,
+                Level::WARN => &self.warn,
+                Level::ERROR => &self.error
+            }
+        }
+
+        /// Width of the widest glyph
+        fn width(&self) -> usize {
+            [
+                &self.trace,
+                &self.debug,
+                &self.info,
+                &self.warn,
+                &self.error
+            ]
+            .into_iter()
+            .map(|glyph| measure_text_width(glyph))
+            .max()
+            .unwrap_or_default()
+        }
+    }
+
+    impl Default for LevelGlyphs {
+        /// Bullets, arrow, warning sign and cross
+        fn default() -> Self {
+            Self {
+                trace: "·".into(),
+                debug: "•".into(),
+                info:  "→".into(),
+                warn:  "⚠".into(),
+                error: "✗".into()
+            }
+        }
+    }
+
+    /// Width right-aligned log is laid out against when its
+    /// stream is not a terminal
+    const FALLBACK_WIDTH: usize = 120;
+
+    /// Least severe level written to stderr, less severe
+    /// events go to stdout
+    pub(super) const STDERR_LEVEL: Level = Level::WARN;
+
+    /// Stream events of `level` are written to
+    pub(super) fn stream(level: Level) -> Stream {
+        if level <= STDERR_LEVEL {
+            Stream::Stderr
+        } else {
+            Stream::Stdout
+        }
+    }
+
+    /// Set per stream whenever the terminal was resized,
+    /// start set so the first call queries it
+    static RESIZED: LazyLock<[Arc<AtomicBool>; 2]> = LazyLock::new(|| {
+        [(); 2].map(|()| {
+            let resized = Arc::new(AtomicBool::new(true));
+            // Without the handler width just stays memoized
+            let _ = signal_hook::flag::register(SIGWINCH, Arc::clone(&resized));
+            resized
+        })
+    });
+
+    /// Last known width per stream, `0` when it's not a
+    /// terminal
+    static WIDTH: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+
+    /// Width of the terminal attached to `stream`, [`None`]
+    /// when it is not a terminal or its size can't be
+    /// queried. Only asks the terminal again after
+    /// `SIGWINCH`
+    pub(super) fn terminal_width(stream: Stream) -> Option<usize> {
+        let width = &WIDTH[stream as usize];
+        if RESIZED[stream as usize].swap(false, Ordering::Relaxed) {
+            let term = match stream {
+                Stream::Stdout => Term::stdout(),
+                Stream::Stderr => Term::stderr()
+            };
+            let cols = term.size_checked().map_or(0, |(_, cols)| cols as usize);
+            width.store(cols, Ordering::Relaxed);
+        }
+        Some(width.load(Ordering::Relaxed)).filter(|cols| *cols > 0)
+    }
+
+    pub(super) struct TracingFormatter {
+        /// Timestamp format
+        pub(super) time:           TimeFormatter,
+        /// Line width of the stream to right-align scopes
+        /// against
+        pub(super) width:          fn(Stream) -> Option<usize>,
+        /// Width used instead when `width` gives nothing,
+        /// [`None`] keeps such lines unaligned
+        pub(super) fallback_width: Option<usize>,
+        /// Upper limit of line width
+        pub(super) max_width:      Option<usize>,
+        /// Handling of lines wider than that
+        pub(super) overflow:       Overflow,
+        /// Whether to show time spent in each span
+        pub(super) span_timing:    bool,
+        /// Symbols shown before level, if any
+        pub(super) glyphs:         Option<LevelGlyphs>,
+        /// URL template source locations link to on
+        /// terminals, if any
+        pub(super) hyperlinks:     Option<String>,
+        /// Upper limit of scope and location width
+        pub(super) scope_width:    Option<usize>,
+        /// Column scope starts at after short messages,
+        /// instead of the right edge
+        pub(super) anchor_column:  Option<usize>,
+        /// Whether to keep colors in the stream
+        pub(super) colorize:       fn(Stream) -> bool
+    }
+
+    impl TracingFormatter {
+        /// Create formatter for terminal, laying out each
+        /// line for the [`stream`] its level goes to
+        pub(super) fn new(time: TimeFormat) -> color_eyre::Result<Self> {
+            Ok(Self {
+                time:           TimeFormatter::new(time, TimeZone::Local)?,
+                width:          terminal_width,
+                fallback_width: Some(FALLBACK_WIDTH),
+                max_width:      None,
+                overflow:       Overflow::default(),
+                span_timing:    false,
+                glyphs:         None,
+                hyperlinks:     None,
+                scope_width:    None,
+                anchor_column:  None,
+                colorize:       should_colorize
+            })
+        }
+    }
 
     impl<S, F> FormatEvent<S, F> for TracingFormatter
     where
@@ -91,116 +948,2211 @@ mod format {
         ) -> Result {
             let meta = event.metadata();
 
-            let left = {
+            let (left, indent) = {
                 let mut buf = String::new();
                 let mut wr = Writer::new(&mut buf);
 
                 // Time
-                super::time::TimeFormatter.format_time(&mut wr)?;
+                self.time.format_time(&mut wr)?;
 
                 // Log level
-                let level = match *meta.level() {
-                    Level::TRACE => "TRACE".purple().to_string(),
-                    Level::DEBUG => "DEBUG".blue().to_string(),
-                    Level::INFO => " INFO".green().to_string(),
-                    Level::WARN => " WARN".yellow().bold().to_string(),
-                    Level::ERROR => "ERROR".red().bold().to_string()
+                let level = *meta.level();
+                let paint = |s: &str| match level {
+                    Level::TRACE => s.purple().to_string(),
+                    Level::DEBUG => s.blue().to_string(),
+                    Level::INFO => s.green().to_string(),
+                    Level::WARN => s.yellow().bold().to_string(),
+                    Level::ERROR => s.red().bold().to_string()
+                };
+                if let Some(glyphs) = &self.glyphs {
+                    // Right-aligned, so levels stay in one
+                    // column whatever glyphs are
+                    let glyph = glyphs.get(level);
+                    let pad = glyphs.width() - measure_text_width(glyph);
+                    write!(wr, "{}{} ", " ".repeat(pad), paint(glyph).dimmed())?;
+                }
+                let name = match level {
+                    Level::TRACE => "TRACE",
+                    Level::DEBUG => "DEBUG",
+                    Level::INFO => " INFO",
+                    Level::WARN => " WARN",
+                    Level::ERROR => "ERROR"
                 };
-                write!(wr, " {} ", level.dimmed())?;
+                write!(wr, "{} ", paint(name).dimmed())?;
 
-                // Message
-                ctx.format_fields(wr.by_ref(), event)?;
+                // Message, continuation lines are indented
+                // past time and level
+                let indent = measure_text_width(&buf);
+                ctx.format_fields(Writer::new(&mut buf), event)?;
 
-                buf
+                (buf, indent)
             };
 
-            let right = {
-                let mut buf = String::new();
-                let mut wr = Writer::new(&mut buf);
-
-                // Target
-                write!(wr, "{}", meta.target().purple().dimmed())?;
-
-                // Spans and their extensions
-                if let Some(scope) = ctx.event_scope() {
-                    write!(wr, "{}", "(".purple().dimmed())?;
-                    let mut spans = Vec::new();
-                    for span in scope.from_root() {
-                        let mut span_info = String::new();
-                        span_info.push_str(&span.metadata().name().dimmed().to_string());
-
-                        if let Some(fields) = span.extensions().get::<FormattedFields<F>>()
+            // Spans and their extensions
+            let spans = ctx.event_scope().map(|scope| {
+                scope
+                    .from_root()
+                    .map(|span| {
+                        let ext = span.extensions();
+                        let fields = if let Some(fields) = ext.get::<SpanFields>() {
+                            fields
+                                .0
+                                .iter()
+                                .map(|(key, value)| (format!("{}: ", key.cyan()), value.colored()))
+                                .collect()
+                        } else if let Some(fields) = ext.get::<FormattedFields<F>>()
                             && !fields.is_empty()
                         {
-                            let fields = {
-                                let mut f = Vec::new();
-                                let pairs = fields.fields.split(' ').collect::<Vec<_>>();
-                                for pair in pairs {
-                                    let pair = strip_ansi_codes(pair);
-                                    let (key, value) = pair.split_once('=').unwrap();
-                                    let key = key.cyan();
-                                    let value = value.cyan();
-                                    let value = value.bold();
-
-                                    f.push(format!("{key}: {value}").dimmed().to_string())
-                                }
-                                f
-                            };
-                            span_info.push_str(&format!(
-                                "{}{}{}",
-                                "(".dimmed(),
-                                fields.join(&", ".dimmed().to_string()),
-                                ")".dimmed()
-                            ));
+                            // Not from `FieldsLayer`
+                            vec![(String::new(), strip_ansi_codes(fields).cyan().to_string())]
+                        } else {
+                            Vec::new()
+                        };
+                        let timing = ext
+                            .get::<SpanStart>()
+                            .filter(|_| self.span_timing)
+                            .map(|SpanStart(start)| format!("[{}]", elapsed(start.elapsed())));
+                        ScopeSpan {
+                            name: span.metadata().name(),
+                            fields,
+                            timing
                         }
-                        spans.push(span_info);
-                    }
-                    write!(
-                        wr,
-                        "{}{}",
-                        spans.join(&", ".dimmed().to_string()),
-                        ")".purple().dimmed()
-                    )?;
-                };
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            // Location in source code
+            let file_line = format!(
+                "{}:{}",
+                meta.file().unwrap_or("<unknown>.rs").blue(),
+                meta.line()
+                    .map(|l| l.to_string())
+                    .unwrap_or("??".into())
+                    .blue()
+            )
+            .dimmed()
+            .underline()
+            .to_string();
+            let location = format!(" {file_line} ");
 
-                // Location in source code
-                write!(
-                    wr,
-                    " {} ",
-                    format!(
-                        "{}:{}",
-                        meta.file().unwrap_or("<unknown>.rs").blue(),
-                        meta.line()
-                            .map(|l| l.to_string())
-                            .unwrap_or("??".into())
-                            .blue()
-                    )
-                    .dimmed()
-                    .underline()
-                )?;
-
-                buf
+            let scope = match self.scope_width {
+                None => scope(meta.target(), spans.as_deref(), None, None),
+                Some(budget) => fit_scope(
+                    meta.target(),
+                    spans.as_deref(),
+                    budget.saturating_sub(measure_text_width(&location))
+                )
             };
 
-            let width = measure_text_width(&left) + measure_text_width(&right);
-            let term_width = Term::stdout().size().1 as usize;
-            let spaces = if width < term_width {
-                term_width - width
-            } else {
-                1
+            let parts = Parts {
+                left: &left,
+                indent,
+                scope: &scope,
+                location: &location
+            };
+            let stream = stream(*meta.level());
+            let width = (self.width)(stream).or(self.fallback_width);
+            let line = match (width, self.max_width) {
+                // Not a terminal, nothing to align against
+                (None, None) => format!("{left} {scope}{location}"),
+                (None, Some(max)) => layout(&parts, max, false, None, self.overflow),
+                (Some(term_width), max) => {
+                    let width = max.map_or(term_width, |max| max.min(term_width));
+                    layout(&parts, width, true, self.anchor_column, self.overflow)
+                }
             };
-            writeln!(wr, "{left}{}{right}", " ".repeat(spaces))?;
+            if (self.colorize)(stream) {
+                let line = match (&self.hyperlinks, meta.file()) {
+                    // Linked after layout, so escapes never
+                    // count into widths
+                    (Some(url), Some(file)) if (self.width)(stream).is_some() =>
+                        hyperlink(line, &file_line, &location_url(url, file, meta.line())),
+                    _ => line
+                };
+                writeln!(wr, "{line}")
+            } else {
+                writeln!(wr, "{}", strip_ansi_codes(&line))
+            }
+        }
+    }
+
+    /// Span as shown in the scope
+    struct ScopeSpan {
+        /// Span name
+        name:   &'static str,
+        /// Colored field keys with separator, and values
+        fields: Vec<(String, String)>,
+        /// Time spent in span, if shown
+        timing: Option<String>
+    }
+
+    /// Render target and spans, cutting field values and
+    /// span names longer than the limits
+    fn scope(
+        target: &str,
+        spans: Option<&[ScopeSpan]>,
+        value_limit: Option<usize>,
+        name_limit: Option<usize>
+    ) -> String {
+        let cut = |s: &str, limit: Option<usize>| match limit {
+            Some(limit) => truncate_str(s, limit, "…").into_owned(),
+            None => s.to_owned()
+        };
+
+        let mut buf = target.purple().dimmed().to_string();
+        let Some(spans) = spans else {
+            return buf;
+        };
+        let spans = spans
+            .iter()
+            .map(|span| {
+                let mut info = cut(span.name, name_limit).dimmed().to_string();
+                if !span.fields.is_empty() {
+                    let fields = span
+                        .fields
+                        .iter()
+                        .map(|(key, value)| {
+                            format!("{key}{}", cut(value, value_limit))
+                                .dimmed()
+                                .to_string()
+                        })
+                        .collect::<Vec<_>>();
+                    info.push_str(&format!(
+                        "{}{}{}",
+                        "(".dimmed(),
+                        fields.join(&", ".dimmed().to_string()),
+                        ")".dimmed()
+                    ));
+                }
+                if let Some(timing) = &span.timing {
+                    info.push_str(&format!(" {}", timing.yellow().dimmed()));
+                }
+                info
+            })
+            .collect::<Vec<_>>();
+        buf.push_str(&format!(
+            "{}{}{}",
+            "(".purple().dimmed(),
+            spans.join(&", ".dimmed().to_string()),
+            ")".purple().dimmed()
+        ));
+        buf
+    }
+
+    /// Render scope into `room` columns, cutting field
+    /// values first, span names next and the whole scope
+    /// last
+    fn fit_scope(
+        target: &str,
+        spans: Option<&[ScopeSpan]>,
+        room: usize
+    ) -> String {
+        let fits = |s: &String| measure_text_width(s) <= room;
+        let all = spans.unwrap_or_default();
+        let longest_value = all
+            .iter()
+            .flat_map(|span| &span.fields)
+            .map(|(_, value)| measure_text_width(value))
+            .max()
+            .unwrap_or_default();
+        let longest_name = all
+            .iter()
+            .map(|span| measure_text_width(span.name))
+            .max()
+            .unwrap_or_default();
+
+        let full = scope(target, spans, None, None);
+        if fits(&full) {
+            return full;
+        }
+        let by_value = |limit| scope(target, spans, Some(limit), None);
+        if let Some(limit) = widest(longest_value, |limit| fits(&by_value(limit))) {
+            return by_value(limit);
+        }
+        let by_name = |limit| scope(target, spans, Some(1), Some(limit));
+        if let Some(limit) = widest(longest_name, |limit| fits(&by_name(limit))) {
+            return by_name(limit);
+        }
+        let shortest = by_name(1);
+        truncate_str(&shortest, room, &"…".dimmed().to_string()).into_owned()
+    }
+
+    /// Largest limit below `max` that `fits`, found by
+    /// bisection since longer limits never render
+    /// narrower
+    pub(super) fn widest(
+        max: usize,
+        fits: impl Fn(usize) -> bool
+    ) -> Option<usize> {
+        // `fits(low)` holds, `high` is known not to
+        let (mut low, mut high) = (1, max);
+        if low >= high || !fits(low) {
+            return None;
+        }
+        while high - low > 1 {
+            let mid = low + (high - low) / 2;
+            if fits(mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Some(low)
+    }
+
+    /// URL of `file` at `line` made from `template`, see
+    /// [`FILE_URL`](super::FILE_URL). Relative paths, which
+    /// workspace crates have, are resolved against
+    /// workspace root disko was built in
+    pub(super) fn location_url(
+        template: &str,
+        file: &str,
+        line: Option<u32>
+    ) -> String {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .unwrap_or(Path::new("/"));
+        // Absolute `file` replaces `root`
+        let path = root.join(file);
+        template
+            .replace("{path}", &path.display().to_string())
+            .replace("{line}", &line.unwrap_or(1).to_string())
+    }
+
+    /// Make the last `text` in `line` an OSC 8 link to
+    /// `url`. Nothing changes when `text` was cut off
+    fn hyperlink(
+        line: String,
+        text: &str,
+        url: &str
+    ) -> String {
+        match line.rfind(text) {
+            Some(start) => {
+                let (head, tail) = line.split_at(start);
+                let tail = &tail[text.len()..];
+                format!("{head}\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\{tail}")
+            },
+            None => line
+        }
+    }
+
+    /// Pieces of a log line
+    struct Parts<'a> {
+        /// Time, level and message
+        left:     &'a str,
+        /// Width of time and level
+        indent:   usize,
+        /// Target and spans
+        scope:    &'a str,
+        /// Source location
+        location: &'a str
+    }
+
+    /// Join `left` and `right` with a single space, or with
+    /// enough of them to fill `width` when `pad` is set.
+    /// With `anchor`, `right` starts at that column
+    /// instead when both fit
+    fn join(
+        left: &str,
+        right: &str,
+        width: usize,
+        pad: bool,
+        anchor: Option<usize>
+    ) -> String {
+        let (left_width, right_width) = (measure_text_width(left), measure_text_width(right));
+        let spaces = match anchor {
+            _ if !pad => 1,
+            Some(column) if left_width < column && column + right_width <= width =>
+                column - left_width,
+            _ => width.saturating_sub(left_width + right_width).max(1)
+        };
+        format!("{left}{}{right}", " ".repeat(spaces))
+    }
+
+    /// Fit message and scope into `width` columns,
+    /// right-aligning the scope when `pad` is set, or
+    /// starting it at `anchor` column when message is
+    /// shorter
+    fn layout(
+        parts: &Parts<'_>,
+        width: usize,
+        pad: bool,
+        anchor: Option<usize>,
+        overflow: Overflow
+    ) -> String {
+        let left = parts.left;
+        let right = format!("{}{}", parts.scope, parts.location);
+        let left_width = measure_text_width(left);
+        let right_width = measure_text_width(&right);
+        if left_width + right_width < width {
+            return join(left, &right, width, pad, anchor);
+        }
+
+        match overflow {
+            Overflow::Truncate => {},
+            Overflow::Elide => {
+                let room =
+                    width.saturating_sub(left_width + 1 + measure_text_width(parts.location));
+                if room >= MIN_SCOPE_WIDTH {
+                    let scope = truncate_str(parts.scope, room, "…");
+                    let right = format!("{scope}{}", parts.location);
+                    return join(left, &right, width, pad, anchor);
+                }
+            },
+            Overflow::Wrap => {
+                let room = width.saturating_sub(right_width + 1);
+                if room >= parts.indent + MIN_MESSAGE_WIDTH {
+                    let indent = " ".repeat(parts.indent);
+                    let options = textwrap::Options::new(room).subsequent_indent(&indent);
+                    let lines = textwrap::wrap(left, options);
+                    let mut out = join(&lines[0], &right, width, pad, anchor);
+                    for line in &lines[1..] {
+                        out.push('\n');
+                        out.push_str(line);
+                    }
+                    return out;
+                }
+            }
+        }
 
-            Ok(())
+        if width.saturating_sub(left_width + 1) >= MIN_SCOPE_WIDTH {
+            // Shorten scope to keep a gap
+            let right = truncate_str(&right, width - left_width - 1, "…");
+            format!("{left} {right}")
+        } else {
+            // No room left, scope goes below. Ellipsis is
+            // kept even when `width` has no room for it
+            let right = truncate_str(&right, width, "…");
+            let spaces = if pad {
+                width.saturating_sub(measure_text_width(&right))
+            } else {
+                0
+            };
+            format!("{left}\n{}{right}", " ".repeat(spaces))
         }
     }
 }
 
-/// Install trace dispatcher
-pub fn install() -> Result<()> {
-    let subscriber = tracing_subscriber::fmt()
-        .event_format(format::TracingFormatter)
-        .finish();
+mod json {
+    //! ## JSON
+    //!
+    //! One JSON object per event, for machines reading the
+    //! log. Fields keep the types [`FieldsLayer`] recorded
+    //!
+    //! [`FieldsLayer`]: super::fields::FieldsLayer
+
+    use std::fmt::Result;
+    use std::time::{
+        SystemTime,
+        UNIX_EPOCH
+    };
 
-    tracing::subscriber::set_global_default(subscriber).context("Failed to install log")
+    use serde_json::{
+        Map,
+        Value,
+        json
+    };
+    use tracing::{
+        Event,
+        Subscriber
+    };
+    use tracing_subscriber::fmt::format::Writer;
+    use tracing_subscriber::fmt::{
+        FmtContext,
+        FormatEvent,
+        FormatFields
+    };
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::fields::{
+        FieldValue,
+        SpanFields
+    };
+    use crate::private::redact::Secrets;
+
+    /// Formatter writing events as JSON lines, event fields
+    /// among `0` redacted
+    #[derive(Default)]
+    pub(super) struct JsonFormatter(pub(super) Secrets);
+
+    /// JSON value of the same type as `value`, falling back
+    /// to a string when JSON can't represent it
+    fn value(value: &FieldValue) -> Value {
+        match value {
+            FieldValue::Bool(b) => Value::Bool(*b),
+            FieldValue::Int(i) =>
+                i64::try_from(*i).map_or_else(|_| i.to_string().into(), Value::from),
+            FieldValue::Uint(u) =>
+                u64::try_from(*u).map_or_else(|_| u.to_string().into(), Value::from),
+            FieldValue::Float(f) =>
+                serde_json::Number::from_f64(*f).map_or_else(|| f.to_string().into(), Value::Number),
+            FieldValue::Str(s) | FieldValue::Debug(s) => Value::String(s.clone()),
+            FieldValue::Redacted => Value::String(value.to_string())
+        }
+    }
+
+    fn object(fields: &SpanFields) -> Map<String, Value> {
+        fields
+            .0
+            .iter()
+            .map(|(key, v)| ((*key).into(), value(v)))
+            .collect()
+    }
+
+    impl<S, F> FormatEvent<S, F> for JsonFormatter
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        F: for<'a> FormatFields<'a> + 'static
+    {
+        fn format_event(
+            &self,
+            ctx: &FmtContext<'_, S, F>,
+            mut wr: Writer<'_>,
+            event: &Event<'_>
+        ) -> Result {
+            let meta = event.metadata();
+
+            let mut fields = SpanFields::default();
+            event.record(&mut fields);
+            fields.redact(&self.0);
+            let message = fields
+                .0
+                .iter()
+                .position(|(key, _)| *key == "message")
+                .map(|i| fields.0.remove(i).1.to_string());
+
+            let scope = ctx
+                .event_scope()
+                .map(|scope| {
+                    scope
+                        .from_root()
+                        .map(|span| {
+                            let ext = span.extensions();
+                            json!({
+                                "name": span.name(),
+                                "fields": ext.get::<SpanFields>().map(object).unwrap_or_default()
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+
+            let line = json!({
+                "timestamp": timestamp,
+                "level": meta.level().as_str(),
+                "target": meta.target(),
+                "message": message,
+                "fields": object(&fields),
+                "file": meta.file(),
+                "line": meta.line(),
+                "scope": scope
+            });
+            writeln!(wr, "{line}")
+        }
+    }
+}
+
+mod progress {
+    //! ## Progress
+    //!
+    //! Keeps [`indicatif`] progress bars intact while log
+    //! lines are written
+
+    use std::io::{
+        Result,
+        Write
+    };
+    use std::sync::{
+        PoisonError,
+        RwLock
+    };
+
+    use indicatif::MultiProgress;
+    use tracing::Metadata;
+    use tracing_subscriber::fmt::MakeWriter;
+
+    /// Bars log lines are printed above
+    static BARS: RwLock<Option<MultiProgress>> = RwLock::new(None);
+
+    /// Print log lines above `bars`, or straight to the
+    /// terminal again if [`None`]
+    pub fn set_progress_bars(bars: Option<MultiProgress>) {
+        *BARS.write().unwrap_or_else(PoisonError::into_inner) = bars;
+    }
+
+    /// Run `f` with registered bars hidden
+    fn suspend<T>(f: impl FnOnce() -> T) -> T {
+        match &*BARS.read().unwrap_or_else(PoisonError::into_inner) {
+            Some(bars) => bars.suspend(f),
+            None => f()
+        }
+    }
+
+    /// Writer clearing registered bars while writing and
+    /// redrawing them after
+    pub(super) struct Suspending<W>(pub(super) W);
+
+    impl<W: Write> Write for Suspending<W> {
+        fn write(
+            &mut self,
+            buf: &[u8]
+        ) -> Result<usize> {
+            suspend(|| self.0.write(buf))
+        }
+
+        /// Whole log line at once, so bars are redrawn once
+        /// per line
+        fn write_all(
+            &mut self,
+            buf: &[u8]
+        ) -> Result<()> {
+            suspend(|| self.0.write_all(buf))
+        }
+
+        fn flush(&mut self) -> Result<()> { self.0.flush() }
+    }
+
+    impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for Suspending<M> {
+        type Writer = Suspending<M::Writer>;
+
+        fn make_writer(&'a self) -> Self::Writer { Suspending(self.0.make_writer()) }
+
+        fn make_writer_for(
+            &'a self,
+            meta: &Metadata<'_>
+        ) -> Self::Writer {
+            Suspending(self.0.make_writer_for(meta))
+        }
+    }
+}
+
+#[cfg(feature = "journald")]
+mod journald {
+    //! ## Journald
+    //!
+    //! Sends events to systemd journal over its native
+    //! protocol, with span fields as journal fields
+
+    use std::os::unix::net::UnixDatagram;
+    use std::path::PathBuf;
+
+    use color_eyre::eyre::Context as _;
+    use tracing::{
+        Event,
+        Level,
+        Subscriber
+    };
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::Context;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use super::fields::SpanFields;
+    use crate::private::redact::Secrets;
+
+    /// Socket journald listens on
+    const SOCKET: &str = "/run/systemd/journal/socket";
+
+    /// Fields every entry sets itself
+    const RESERVED: &[&str] = &[
+        "PRIORITY",
+        "SYSLOG_IDENTIFIER",
+        "TARGET",
+        "CODE_FILE",
+        "CODE_LINE",
+        "MESSAGE"
+    ];
+
+    /// Syslog priority of `level`
+    pub(super) fn priority(level: Level) -> u8 {
+        match level {
+            Level::ERROR => 3,
+            Level::WARN => 4,
+            Level::INFO => 6,
+            Level::DEBUG | Level::TRACE => 7
+        }
+    }
+
+    /// Journal field name made of `name`: uppercase
+    /// letters, digits and underscores, starting with a
+    /// letter. Names starting otherwise or clashing with
+    /// [`RESERVED`] fields get `FIELD_` prefix
+    pub(super) fn key(name: &str) -> String {
+        let key = name
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect::<String>();
+        let key = key.trim_start_matches('_');
+        let key = match key.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() && !RESERVED.contains(&key) => key.into(),
+            _ => format!("FIELD_{key}")
+        };
+        key.chars().take(64).collect()
+    }
+
+    /// Append `key` with `value` to `buf`. Multiline values
+    /// are prefixed with their length instead
+    pub(super) fn field(
+        buf: &mut Vec<u8>,
+        key: &str,
+        value: &str
+    ) {
+        buf.extend_from_slice(key.as_bytes());
+        if value.contains('\n') {
+            buf.push(b'\n');
+            buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        } else {
+            buf.push(b'=');
+        }
+        buf.extend_from_slice(value.as_bytes());
+        buf.push(b'\n');
+    }
+
+    /// Layer sending every event to journald
+    pub(super) struct JournaldLayer {
+        /// Unbound socket entries are sent from
+        socket:     UnixDatagram,
+        /// Where journald listens
+        path:       PathBuf,
+        /// `SYSLOG_IDENTIFIER` of entries
+        identifier: String,
+        /// Event fields whose values are redacted
+        secrets:    Secrets
+    }
+
+    impl JournaldLayer {
+        /// Layer sending to journald, failing when it's not
+        /// running
+        pub(super) fn new(secrets: Secrets) -> color_eyre::Result<Self> {
+            Self::with_socket(SOCKET.into(), secrets)
+        }
+
+        /// Layer sending to journal listening on `path`
+        pub(super) fn with_socket(
+            path: PathBuf,
+            secrets: Secrets
+        ) -> color_eyre::Result<Self> {
+            if !path.exists() {
+                color_eyre::eyre::bail!("journald is not running, no socket at {}", path.display());
+            }
+            let socket = UnixDatagram::unbound().context("Failed to create journald socket")?;
+            let identifier = std::env::args_os()
+                .next()
+                .as_deref()
+                .map(std::path::Path::new)
+                .and_then(|exe| exe.file_name())
+                .map_or("disko".into(), |name| name.to_string_lossy().into_owned());
+            Ok(Self {
+                socket,
+                path,
+                identifier,
+                secrets
+            })
+        }
+    }
+
+    impl<S> Layer<S> for JournaldLayer
+    where S: Subscriber + for<'a> LookupSpan<'a>
+    {
+        fn on_event(
+            &self,
+            event: &Event<'_>,
+            ctx: Context<'_, S>
+        ) {
+            let meta = event.metadata();
+            let mut buf = Vec::new();
+            field(&mut buf, "PRIORITY", &priority(*meta.level()).to_string());
+            field(&mut buf, "SYSLOG_IDENTIFIER", &self.identifier);
+            field(&mut buf, "TARGET", meta.target());
+            if let Some(file) = meta.file() {
+                field(&mut buf, "CODE_FILE", file);
+            }
+            if let Some(line) = meta.line() {
+                field(&mut buf, "CODE_LINE", &line.to_string());
+            }
+
+            let mut fields = SpanFields::default();
+            event.record(&mut fields);
+            fields.redact(&self.secrets);
+            for (name, value) in &fields.0 {
+                let key = match *name {
+                    "message" => "MESSAGE".into(),
+                    name => key(name)
+                };
+                field(&mut buf, &key, &value.to_string());
+            }
+
+            // Span fields are prefixed with span name,
+            // e.g. `MOUNT_DEVICE`
+            if let Some(scope) = ctx.event_scope(event) {
+                for span in scope.from_root() {
+                    let ext = span.extensions();
+                    let Some(fields) = ext.get::<SpanFields>() else {
+                        continue;
+                    };
+                    for (name, value) in &fields.0 {
+                        let key = key(&format!("{}_{name}", span.name()));
+                        field(&mut buf, &key, &value.to_string());
+                    }
+                }
+            }
+
+            // Nowhere to report failures to, journal being
+            // gone (or the entry too large for a datagram)
+            // only loses the entry
+            let _ = self.socket.send_to(&buf, &self.path);
+        }
+    }
+}
+
+pub use format::{
+    LevelGlyphs,
+    Overflow
+};
+pub use progress::set_progress_bars;
+pub use time::{
+    TimeFormat,
+    TimeZone
+};
+
+/// In-memory log sink, e.g. for asserting on log lines in
+/// tests. Clones share the same buffer
+#[derive(Debug, Clone, Default)]
+pub struct MemoryWriter(Arc<Mutex<Vec<u8>>>);
+
+impl MemoryWriter {
+    /// Everything written so far
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap_or_else(PoisonError::into_inner)).into()
+    }
+
+    /// Like [`contents`](Self::contents), but without
+    /// colors
+    pub fn plain(&self) -> String { console::strip_ansi_codes(&self.contents()).into() }
+}
+
+impl std::io::Write for MemoryWriter {
+    fn write(
+        &mut self,
+        buf: &[u8]
+    ) -> std::io::Result<usize> {
+        self.0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> { Ok(()) }
+}
+
+impl<'a> MakeWriter<'a> for MemoryWriter {
+    type Writer = Self;
+
+    fn make_writer(&'a self) -> Self::Writer { self.clone() }
+}
+
+/// Keeps buffered log sinks writing, flushing them when
+/// dropped. Hold it until the program exits
+#[must_use = "dropping the guard stops buffered log sinks"]
+#[derive(Debug, Default)]
+pub struct LoggingGuard {
+    /// Empty unless logging to a file
+    workers: Vec<WorkerGuard>
+}
+
+impl LoggingGuard {
+    /// Guard keeping `worker` writing
+    pub(crate) fn with_worker(worker: WorkerGuard) -> Self {
+        Self {
+            workers: vec![worker]
+        }
+    }
+}
+
+impl Drop for LoggingGuard {
+    /// Flush buffered sinks, waiting for their workers to
+    /// write what's left
+    fn drop(&mut self) { self.workers.clear() }
+}
+
+/// Level shown when neither `RUST_LOG` nor explicit
+/// directives say otherwise
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::INFO;
+
+/// Level shown at `verbosity`, e.g. count of `-v` minus
+/// count of `-q`: `0` is [`DEFAULT_LEVEL`], `1` and `2`
+/// step up to `DEBUG` and `TRACE`, `-1` and `-2` down to
+/// `WARN` and `ERROR`, `-3` and below silence log
+fn verbosity_level(verbosity: i8) -> LevelFilter {
+    match verbosity {
+        ..=-3 => LevelFilter::OFF,
+        -2 => LevelFilter::ERROR,
+        -1 => LevelFilter::WARN,
+        0 => DEFAULT_LEVEL,
+        1 => LevelFilter::DEBUG,
+        2.. => LevelFilter::TRACE
+    }
+}
+
+/// Filter from `RUST_LOG`, showing `default` level and
+/// above when it's unset, see [`lossy_filter`]
+fn env_filter(default: LevelFilter) -> (EnvFilter, Option<String>) {
+    lossy_filter(&std::env::var("RUST_LOG").unwrap_or_default(), default)
+}
+
+/// Filter from `directives`, skipping invalid ones rather
+/// than failing, since a typo in inherited `RUST_LOG`
+/// shouldn't stop disko. Returns warning about skipped
+/// ones along
+fn lossy_filter(
+    directives: &str,
+    default: LevelFilter
+) -> (EnvFilter, Option<String>) {
+    let builder = || EnvFilter::builder().with_default_directive(default.into());
+    let warning = builder()
+        .parse(directives)
+        .err()
+        .map(|e| format!("Ignoring invalid parts of `RUST_LOG`: {e}"));
+    (builder().parse_lossy(directives), warning)
+}
+
+/// Filter from explicit `directives`
+fn filter(directives: &str) -> Result<EnvFilter> {
+    EnvFilter::builder()
+        .with_default_directive(DEFAULT_LEVEL.into())
+        .parse(directives)
+        .with_context(|| format!("Invalid log filter `{directives}`"))
+}
+
+/// Everything log is configured with, collected by
+/// [`DiagnosticsBuilder`]
+#[derive(Default)]
+pub(crate) struct LogOptions {
+    /// Directives used instead of `RUST_LOG`
+    pub(crate) filter:      Option<String>,
+    /// Verbosity used when `RUST_LOG` is unset
+    pub(crate) verbosity:   i8,
+    /// File every event is also written to
+    pub(crate) file:        Option<PathBuf>,
+    /// Whether to write JSON objects instead of lines
+    pub(crate) json:        bool,
+    /// Sink used instead of the terminal
+    pub(crate) writer:      Option<BoxMakeWriter>,
+    /// Whether to send events to journald
+    #[cfg(feature = "journald")]
+    pub(crate) journald:    bool,
+    /// Timestamp format
+    pub(crate) time:        TimeFormat,
+    /// Zone of wall-clock timestamps
+    pub(crate) time_zone:   TimeZone,
+    /// Handling of lines wider than the terminal
+    pub(crate) overflow:    Overflow,
+    /// Whether to show time spent in each span
+    pub(crate) span_timing: bool,
+    /// Upper limit of line width
+    pub(crate) max_width:   Option<usize>,
+    /// Symbols shown before level
+    pub(crate) glyphs:      Option<LevelGlyphs>,
+    /// URL template source locations link to
+    pub(crate) hyperlinks:  Option<String>,
+    /// Upper limit of scope and location width
+    pub(crate) scope_width: Option<usize>,
+    /// Column scope starts at after short messages
+    pub(crate) anchor:      Option<usize>,
+    /// Fields redacted on top of
+    /// [`REDACTED_FIELDS`](crate::private::redact::REDACTED_FIELDS)
+    pub(crate) redacted:    Vec<String>
+}
+
+/// Install trace dispatcher, filtered by `RUST_LOG`.
+/// Warnings and errors go to stderr, everything else to
+/// stdout
+pub fn install() -> Result<LoggingGuard> { DiagnosticsBuilder::new().install_log() }
+
+/// Install trace dispatcher filtered by `RUST_LOG` that
+/// writes to `writer`, e.g. a [`MemoryWriter`], instead of
+/// stdout. Lines are formatted as piped stdout would be,
+/// colored only with
+/// [`ColorChoice::Always`](crate::ColorChoice::Always)
+pub fn install_to_writer(
+    writer: impl for<'w> MakeWriter<'w> + Send + Sync + 'static
+) -> Result<LoggingGuard> {
+    DiagnosticsBuilder::new().with_writer(writer).install_log()
+}
+
+/// Install trace dispatcher writing one JSON object per
+/// event, filtered by `RUST_LOG`
+pub fn install_json() -> Result<LoggingGuard> { DiagnosticsBuilder::new().json(true).install_log() }
+
+/// Hyperlink template opening source location as a file,
+/// see [`DiagnosticsBuilder::hyperlinks`]
+pub const FILE_URL: &str = "file://{path}";
+
+/// Column worth passing to
+/// [`DiagnosticsBuilder::anchor_column`]
+pub const ANCHOR_COLUMN: usize = 60;
+
+/// Install trace dispatcher showing only what `directives`
+/// allow, in `RUST_LOG` syntax (e.g.
+/// `warn,disko_lib=debug`)
+pub fn install_with_filter(directives: &str) -> Result<LoggingGuard> {
+    DiagnosticsBuilder::new()
+        .with_filter(directives)
+        .install_log()
+}
+
+/// Install trace dispatcher showing more with positive
+/// `verbosity` and less with negative one, see
+/// [`DiagnosticsBuilder::verbosity`]. `RUST_LOG` wins when
+/// it's set
+pub fn install_with_verbosity(verbosity: i8) -> Result<LoggingGuard> {
+    DiagnosticsBuilder::new().verbosity(verbosity).install_log()
+}
+
+/// Install trace dispatcher filtered by `RUST_LOG`, which
+/// also writes every event to `path` without colors. Keep
+/// returned guard alive until exit, dropping it flushes the
+/// file
+pub fn install_with_file(path: &Path) -> Result<LoggingGuard> {
+    DiagnosticsBuilder::new().with_file(path).install_log()
+}
+
+/// Install trace dispatcher sending every event to
+/// journald, see [`DiagnosticsBuilder::journald`]
+#[cfg(feature = "journald")]
+pub fn install_journald() -> Result<LoggingGuard> {
+    DiagnosticsBuilder::new().journald().install_log()
+}
+
+/// Trace dispatcher built from [`LogOptions`], not
+/// installed yet
+pub(crate) struct PreparedLog {
+    /// [`None`] when some dispatcher was installed already
+    dispatch: Option<Dispatch>,
+    guard:    LoggingGuard,
+    /// Logged once installed
+    warning:  Option<String>,
+    /// Shared with crash context once installed
+    secrets:  Secrets
+}
+
+impl PreparedLog {
+    /// Install dispatcher globally, see [`set_global`]
+    pub(crate) fn install(self) -> LoggingGuard {
+        match self.dispatch {
+            Some(dispatch) => set_global(dispatch),
+            None => tracing::debug!("Log is already installed, keeping it")
+        }
+        self.secrets.register();
+        if let Some(warning) = self.warning {
+            tracing::warn!("{warning}");
+        }
+        self.guard
+    }
+}
+
+/// Build trace dispatcher configured with `options`, see
+/// [`build`]. When some dispatcher was installed already,
+/// only explicit filter is checked, since nothing would
+/// write to files or sinks opened now
+pub(crate) fn prepare(options: LogOptions) -> Result<PreparedLog> {
+    if !tracing::dispatcher::has_been_set() {
+        return build(options);
+    }
+    if let Some(directives) = &options.filter {
+        filter(directives)?;
+    }
+    Ok(PreparedLog {
+        dispatch: None,
+        guard:    LoggingGuard::default(),
+        warning:  None,
+        secrets:  Secrets::with(&options.redacted)
+    })
+}
+
+/// Build trace dispatcher configured with `options`. Fails
+/// on invalid explicit filter, unwritable file or missing
+/// journald before anything global is touched
+pub(crate) fn build(options: LogOptions) -> Result<PreparedLog> {
+    let directives = || match &options.filter {
+        Some(directives) => filter(directives),
+        None => Ok(env_filter(verbosity_level(options.verbosity)).0)
+    };
+    // Same directives parse the same way again
+    directives()?;
+    let filter = || directives().expect("log filter was checked");
+    let warning = match options.filter {
+        Some(_) => None,
+        None => env_filter(verbosity_level(options.verbosity)).1
+    };
+    let secrets = Secrets::with(&options.redacted);
+    let (file, guard) = match &options.file {
+        Some(path) => {
+            let (writer, worker) = file_writer(path)?;
+            (Some(writer), LoggingGuard::with_worker(worker))
+        },
+        None => (None, LoggingGuard::default())
+    };
+
+    let (sink, terminal): (Sink, bool) = (None, true);
+    #[cfg(feature = "journald")]
+    let (sink, terminal): (Sink, bool) = if options.journald {
+        let journald = journald::JournaldLayer::new(secrets.clone())?.with_filter(filter());
+        // Units' stdout ends up in journal already
+        (Some(Box::new(journald)), console::Term::stdout().is_term())
+    } else {
+        (sink, terminal)
+    };
+
+    let custom = options.writer.is_some();
+    let writer = match options.writer {
+        Some(writer) => Some(writer),
+        None if options.json => Some(BoxMakeWriter::new(std::io::stdout)),
+        None => terminal.then(|| BoxMakeWriter::new(console()))
+    };
+    if options.json {
+        let dispatch = Some(Dispatch::new(subscriber(
+            json::JsonFormatter(secrets.clone()),
+            filter,
+            secrets.clone(),
+            sink,
+            writer,
+            file
+        )));
+        return Ok(PreparedLog {
+            dispatch,
+            guard,
+            warning,
+            secrets
+        });
+    }
+
+    let defaults = format::TracingFormatter::new(TimeFormat::default())?;
+    let formatter = format::TracingFormatter {
+        time: time::TimeFormatter::new(options.time, options.time_zone)?,
+        // Writer is not a terminal, whatever stdout is
+        width: if custom { |_| None } else { defaults.width },
+        colorize: if custom {
+            crate::private::color::should_colorize_piped
+        } else {
+            defaults.colorize
+        },
+        overflow: options.overflow,
+        span_timing: options.span_timing,
+        max_width: options.max_width,
+        glyphs: options.glyphs,
+        hyperlinks: options.hyperlinks,
+        scope_width: options.scope_width,
+        anchor_column: options.anchor,
+        ..defaults
+    };
+    let dispatch = Some(Dispatch::new(subscriber(
+        formatter,
+        filter,
+        secrets.clone(),
+        sink,
+        writer,
+        file
+    )));
+    Ok(PreparedLog {
+        dispatch,
+        guard,
+        warning,
+        secrets
+    })
+}
+
+/// Non-blocking writer appending to `path`, creating
+/// missing parent directories
+fn file_writer(path: &Path) -> Result<(NonBlocking, WorkerGuard)> {
+    if let Some(dir) = path.parent()
+        && !dir.as_os_str().is_empty()
+    {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+    }
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open log file {}", path.display()))?;
+
+    Ok(tracing_appender::non_blocking(file))
+}
+
+/// Layers every dispatcher starts with
+type Base = Layered<Filtered<fields::FieldsLayer, Option<EnvFilter>, Registry>, Registry>;
+
+/// Additional sink seeing every event, e.g. journald
+type Sink = Option<Box<dyn tracing_subscriber::Layer<Base> + Send + Sync>>;
+
+/// Console log written to `writer` if given and filtered by
+/// `filter`, plus `sink` and unfiltered plain log written
+/// to `file` if given. All show values of `secrets` as
+/// `****`. Without `file`, span fields are recorded only
+/// where `filter` allows, so disabled spans and events
+/// stay cheap
+fn subscriber(
+    formatter: impl FormatEvent<Layered<Sink, Base>, fields::RedactingFields> + Send + Sync + 'static,
+    filter: impl Fn() -> EnvFilter,
+    secrets: Secrets,
+    sink: Sink,
+    writer: Option<impl for<'w> MakeWriter<'w> + Send + Sync + 'static>,
+    file: Option<NonBlocking>
+) -> impl Subscriber + Send + Sync {
+    let file = file.map(|writer| {
+        let formatter = format::TracingFormatter {
+            width: |_| None,
+            fallback_width: None,
+            colorize: |_| false,
+            ..format::TracingFormatter::new(TimeFormat::DateTime)
+                .expect("preset time format is valid")
+        };
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(fields::RedactingFields(secrets.clone()))
+            .with_ansi(false)
+            .with_writer(writer)
+            .event_format(formatter)
+    });
+    let console = writer.map(|writer| {
+        tracing_subscriber::fmt::layer()
+            .fmt_fields(fields::RedactingFields(secrets.clone()))
+            .with_writer(writer)
+            .event_format(formatter)
+            .with_filter(filter())
+    });
+    // File wants everything
+    let fields = fields::FieldsLayer::new(secrets).with_filter(file.is_none().then(&filter));
+
+    tracing_subscriber::registry()
+        .with(fields)
+        .with(sink)
+        .with(console)
+        .with(file)
+}
+
+/// Terminal sink, writing warnings and errors to stderr and
+/// everything else to stdout, above progress bars if any
+/// are registered with [`set_progress_bars`]
+fn console() -> impl for<'w> MakeWriter<'w> + Send + Sync + 'static {
+    progress::Suspending(
+        std::io::stderr
+            .with_max_level(format::STDERR_LEVEL)
+            .or_else(std::io::stdout)
+    )
+}
+
+/// Install `dispatch` globally, doing nothing if some
+/// dispatcher was installed already
+fn set_global(dispatch: Dispatch) {
+    crate::private::color::sync_console();
+    // Only fails when a global dispatcher exists
+    if tracing::dispatcher::set_global_default(dispatch).is_err() {
+        tracing::debug!("Log is already installed, keeping it");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use console::strip_ansi_codes;
+    use owo_colors::OwoColorize as _;
+    use tracing_subscriber::layer::SubscriberExt as _;
+
+    use super::MemoryWriter;
+    use super::fields::FieldsLayer;
+    use super::format::TracingFormatter;
+    use super::time::TimeFormat;
+    use crate::private::color::Stream;
+    use crate::private::redact::Secrets;
+
+    /// Run `f` with the disko formatter and return its
+    /// output
+    fn capture(f: impl FnOnce()) -> String { capture_with(formatter(|_| None), f) }
+
+    /// Colored default formatter with explicit line width
+    /// and no fallback
+    fn formatter(width: fn(Stream) -> Option<usize>) -> TracingFormatter {
+        TracingFormatter {
+            width,
+            fallback_width: None,
+            colorize: |_| true,
+            ..TracingFormatter::new(TimeFormat::default()).unwrap()
+        }
+    }
+
+    /// Like [`capture`], but with explicit formatter
+    fn capture_with(
+        formatter: TracingFormatter,
+        f: impl FnOnce()
+    ) -> String {
+        let buf = MemoryWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(formatter)
+            .with_writer(buf.clone())
+            .finish()
+            .with(FieldsLayer::default());
+        tracing::subscriber::with_default(subscriber, f);
+
+        buf.contents()
+    }
+
+    #[test]
+    fn span_fields_keep_types() {
+        let out = capture(|| {
+            let _span = tracing::info_span!(
+                "mount",
+                path = "/mnt/my disk=x",
+                count = 3,
+                delta = -2,
+                ok = true
+            )
+            .entered();
+            tracing::info!("mounted");
+        });
+
+        let plain = strip_ansi_codes(&out);
+        assert!(
+            plain.contains("mount(path: /mnt/my disk=x, count: 3, delta: -2, ok: true)"),
+            "{plain}"
+        );
+        assert!(out.contains(&"/mnt/my disk=x".cyan().to_string()));
+        assert!(out.contains(&3.yellow().to_string()));
+        assert!(out.contains(&(-2).yellow().to_string()));
+        assert!(out.contains(&true.magenta().to_string()));
+    }
+
+    #[test]
+    fn recorded_fields_update_span() {
+        let out = capture(|| {
+            let span = tracing::info_span!("format", device = tracing::field::Empty);
+            let _span = span.enter();
+            span.record("device", "/dev/sda1");
+            tracing::info!("formatting");
+        });
+
+        let plain = strip_ansi_codes(&out);
+        assert!(plain.contains("format(device: /dev/sda1)"), "{plain}");
+    }
+
+    #[test]
+    fn unparsed_fields_are_verbatim() {
+        // Without `FieldsLayer` only formatted fields
+        // exist, which must never be split apart
+        let buf = MemoryWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(formatter(|_| Some(200)))
+            .with_writer(buf.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!(
+                "mount",
+                path = "/mnt/my disk",
+                note = "a b=c \"d\"",
+                ok = true
+            )
+            .entered();
+            let _bare = tracing::info_span!("bare", "no key").entered();
+            tracing::info!("mounted");
+        });
+
+        let plain = buf.plain();
+        assert!(
+            plain.contains(r#"mount(path="/mnt/my disk" note="a b=c \"d\"" ok=true)"#),
+            "{plain}"
+        );
+        assert!(plain.contains("bare(no key)"), "{plain}");
+        assert_eq!(
+            console::measure_text_width(plain.trim_end_matches('\n')),
+            200
+        );
+    }
+
+    #[test]
+    fn span_timing() {
+        let out = capture_with(
+            TracingFormatter {
+                span_timing: true,
+                ..formatter(|_| None)
+            },
+            || {
+                let _disk = tracing::info_span!("disk").entered();
+                let _span = tracing::info_span!("mount", device = "/dev/sda1").entered();
+                tracing::info!("mounted");
+            }
+        );
+        let plain = strip_ansi_codes(&out);
+        assert!(plain.contains("(disk ["), "{plain}");
+        assert!(plain.contains("], mount(device: /dev/sda1) ["), "{plain}");
+        assert!(plain.contains("ms]) "), "{plain}");
+
+        // Off by default
+        let out = capture(|| {
+            let _span = tracing::info_span!("mount").entered();
+            tracing::info!("mounted");
+        });
+        assert!(!strip_ansi_codes(&out).contains("ms]"), "{out}");
+
+        let elapsed = |ms| super::fields::elapsed(std::time::Duration::from_millis(ms));
+        assert_eq!(elapsed(412), "412ms");
+        assert_eq!(elapsed(3_051), "3.05s");
+        assert_eq!(elapsed(127_900), "2m07s");
+    }
+
+    #[test]
+    fn fixed_width_pads_to_columns() {
+        let out = capture_with(formatter(|_| Some(120)), || tracing::info!("hello"));
+
+        let line = out.lines().next().unwrap();
+        assert_eq!(console::measure_text_width(line), 120);
+    }
+
+    #[test]
+    fn no_terminal_is_plain() {
+        let out = capture_with(formatter(|_| None), || tracing::info!("hello"));
+
+        let plain = strip_ansi_codes(&out);
+        assert!(
+            plain.contains(" INFO hello disko_lib::private::tracing::tests "),
+            "{plain}"
+        );
+        assert!(plain.ends_with(" \n"), "{plain:?}");
+
+        // Must not panic on any test runner stdout
+        capture_with(formatter(super::format::terminal_width), || {
+            tracing::info!("hello")
+        });
+    }
+
+    #[test]
+    fn streams_are_laid_out_separately() {
+        use tracing::Level;
+
+        use super::format::stream;
+
+        assert_eq!(stream(Level::ERROR), Stream::Stderr);
+        assert_eq!(stream(Level::WARN), Stream::Stderr);
+        assert_eq!(stream(Level::INFO), Stream::Stdout);
+        assert_eq!(stream(Level::TRACE), Stream::Stdout);
+
+        // Piped stdout, colored terminal on stderr
+        let out = capture_with(
+            TracingFormatter {
+                width: |stream| (stream == Stream::Stderr).then_some(90),
+                fallback_width: Some(110),
+                colorize: |stream| stream == Stream::Stderr,
+                ..formatter(|_| None)
+            },
+            || {
+                tracing::info!("hello");
+                tracing::warn!("careful");
+            }
+        );
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(console::measure_text_width(lines[0]), 110);
+        assert_eq!(lines[0], strip_ansi_codes(lines[0]));
+        assert_eq!(console::measure_text_width(lines[1]), 90);
+        assert_ne!(lines[1], strip_ansi_codes(lines[1]));
+    }
+
+    #[test]
+    fn level_glyphs() {
+        use super::LevelGlyphs;
+
+        let log = |glyphs| {
+            let out = capture_with(
+                TracingFormatter {
+                    glyphs,
+                    ..formatter(|_| Some(80))
+                },
+                || {
+                    tracing::info!("hello");
+                    tracing::error!("failed");
+                }
+            );
+            strip_ansi_codes(&out).into_owned()
+        };
+
+        let plain = log(None);
+        let lines = plain.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains(" INFO hello"), "{plain}");
+        assert!(lines[1].contains(" ERROR failed"), "{plain}");
+
+        let glyphs = log(Some(LevelGlyphs::default()));
+        let lines = glyphs.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains(" →  INFO hello"), "{glyphs}");
+        assert!(lines[1].contains(" ✗ ERROR failed"), "{glyphs}");
+        for line in lines {
+            assert_eq!(console::measure_text_width(line), 80, "{line}");
+        }
+
+        // Wider glyphs are padded to the widest one
+        let glyphs = log(Some(LevelGlyphs {
+            error: "!!".into(),
+            ..LevelGlyphs::ascii()
+        }));
+        let lines = glyphs.lines().collect::<Vec<_>>();
+        assert!(lines[0].contains("  >  INFO hello"), "{glyphs}");
+        assert!(lines[1].contains(" !! ERROR failed"), "{glyphs}");
+        let column = |line: &str| line.find("failed").or(line.find("hello")).unwrap();
+        assert_eq!(column(lines[0]), column(lines[1]));
+    }
+
+    #[test]
+    fn hyperlinks() {
+        let link = |width: fn(Stream) -> Option<usize>, colorize: fn(Stream) -> bool| {
+            capture_with(
+                TracingFormatter {
+                    colorize,
+                    hyperlinks: Some("editor://{path}#{line}".into()),
+                    ..formatter(width)
+                },
+                || tracing::info!("hello")
+            )
+        };
+
+        let out = link(|_| Some(120), |_| true);
+        let line = out.lines().next().unwrap();
+        let url = line.split_once("\x1b]8;;editor://").unwrap().1;
+        let path = url.split_once('#').unwrap().0;
+        assert!(std::path::Path::new(path).is_file(), "{path}");
+        assert!(path.ends_with(file!()), "{path}");
+        assert!(line.ends_with("\x1b]8;;\x1b\\ "), "{line:?}");
+        // Escapes don't take columns
+        let (mut without, mut rest) = (String::new(), line);
+        while let Some((head, tail)) = rest.split_once("\x1b]8;;") {
+            without.push_str(head);
+            rest = tail.split_once("\x1b\\").unwrap().1;
+        }
+        without.push_str(rest);
+        assert_eq!(console::measure_text_width(&without), 120, "{without:?}");
+
+        assert_eq!(
+            super::format::location_url(super::FILE_URL, "/usr/src/lib.rs", Some(3)),
+            "file:///usr/src/lib.rs"
+        );
+
+        // Not a terminal, or colors are off
+        assert!(!link(|_| None, |_| true).contains("\x1b]8"));
+        assert!(!link(|_| Some(120), |_| false).contains("\x1b]8"));
+    }
+
+    #[test]
+    fn widest_limit() {
+        use std::cell::Cell;
+
+        use super::format::widest;
+
+        let calls = Cell::new(0);
+        let fits = |limit| {
+            calls.set(calls.get() + 1);
+            limit <= 700
+        };
+        assert_eq!(widest(4096, fits), Some(700));
+        // Bisected, not tried one by one
+        assert!(calls.get() <= 13, "{}", calls.get());
+
+        assert_eq!(widest(4096, |_| false), None);
+        assert_eq!(widest(1, |_| true), None);
+        assert_eq!(widest(10, |_| true), Some(9));
+    }
+
+    #[test]
+    fn scope_width_budget() {
+        let log = |scope_width| {
+            let out = capture_with(
+                TracingFormatter {
+                    scope_width,
+                    ..formatter(|_| None)
+                },
+                || {
+                    let _disk =
+                        tracing::info_span!("disk", device = "/dev/disk/by-id/nvme-long-one")
+                            .entered();
+                    let _mount = tracing::info_span!("mount_filesystem", path = "/mnt").entered();
+                    tracing::info!("hi");
+                }
+            );
+            let line = out.lines().next().unwrap().to_owned();
+            let start = strip_ansi_codes(&line).find("disko_lib").unwrap();
+            let right = strip_ansi_codes(&line)[start..].to_owned();
+            (line, right)
+        };
+        let target = "disko_lib::private::tracing::tests";
+
+        // No limit by default
+        let (_, right) = log(None);
+        assert!(
+            right.starts_with(&format!(
+                "{target}(disk(device: /dev/disk/by-id/nvme-long-one), mount_filesystem(path: /mnt)) "
+            )),
+            "{right}"
+        );
+        let location = console::measure_text_width(&right) - right.find(") ").unwrap() - 1;
+
+        // Field values go first
+        let (line, right) = log(Some(target.len() + 52 + location));
+        assert!(
+            right.contains("(disk(device: /dev/…), mount_filesystem(path: /mnt)) "),
+            "{right}"
+        );
+        // Escapes are never cut
+        assert!(!strip_ansi_codes(&line).contains('\x1b'), "{line:?}");
+
+        // Then span names
+        let (_, right) = log(Some(target.len() + 36 + location));
+        assert!(
+            right.contains("(disk(device: …), mount_f…(path: …)) "),
+            "{right}"
+        );
+
+        // Then the whole scope, location stays
+        let (line, right) = log(Some(20 + location));
+        assert_eq!(
+            console::measure_text_width(&right),
+            20 + location,
+            "{right}"
+        );
+        assert!(right.starts_with("disko_lib::private:… "), "{right}");
+        assert!(line.contains(&"…".dimmed().to_string()), "{line:?}");
+        assert!(ends_with_location(&right), "{right}");
+    }
+
+    #[test]
+    fn anchored_scope() {
+        let log = || {
+            tracing::info!("short");
+            tracing::info!("a bit longer message");
+            tracing::info!("{}", "long ".repeat(8));
+        };
+        let out = capture_with(
+            TracingFormatter {
+                anchor_column: Some(50),
+                ..formatter(|_| Some(140))
+            },
+            log
+        );
+        let plain = strip_ansi_codes(&out);
+        let lines = plain.lines().collect::<Vec<_>>();
+        let column = |line: &str| line.find("disko_lib").unwrap();
+        assert_eq!(column(lines[0]), 50, "{plain}");
+        assert_eq!(column(lines[1]), 50, "{plain}");
+        assert!(console::measure_text_width(lines[0]) < 140, "{plain}");
+        // Longer messages pad to the right edge
+        assert!(column(lines[2]) > 50, "{plain}");
+        assert_eq!(console::measure_text_width(lines[2]), 140, "{plain}");
+
+        // Scope not fitting after the column goes right
+        let out = capture_with(
+            TracingFormatter {
+                anchor_column: Some(120),
+                ..formatter(|_| Some(140))
+            },
+            log
+        );
+        let plain = strip_ansi_codes(&out);
+        for line in plain.lines() {
+            assert_eq!(console::measure_text_width(line), 140, "{plain}");
+        }
+    }
+
+    #[test]
+    fn piped_uses_fallback_width() {
+        let out = capture_with(
+            TracingFormatter {
+                fallback_width: Some(100),
+                ..formatter(|_| None)
+            },
+            || tracing::info!("hello")
+        );
+        let line = out.lines().next().unwrap();
+        assert_eq!(console::measure_text_width(line), 100);
+
+        // Real terminal wins over fallback
+        let out = capture_with(
+            TracingFormatter {
+                fallback_width: Some(100),
+                ..formatter(|_| Some(90))
+            },
+            || tracing::info!("hello")
+        );
+        assert_eq!(console::measure_text_width(out.lines().next().unwrap()), 90);
+
+        // Cached width stays the same between calls
+        assert_eq!(
+            super::format::terminal_width(Stream::Stdout),
+            super::format::terminal_width(Stream::Stdout)
+        );
+    }
+
+    #[test]
+    fn long_scope_is_truncated() {
+        let out = capture_with(formatter(|_| Some(80)), long_scope);
+
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1, "{out}");
+        assert!(console::measure_text_width(lines[0]) <= 80);
+
+        let plain = strip_ansi_codes(lines[0]);
+        assert!(plain.contains("formatting disko_lib"), "{plain}");
+        assert!(plain.ends_with('…'), "{plain}");
+    }
+
+    #[test]
+    fn long_message_moves_scope_to_next_line() {
+        let out = capture_with(formatter(|_| Some(60)), || {
+            let _span = tracing::info_span!("mount", device = "/dev/sda1").entered();
+            tracing::info!("{}", "a very long message ".repeat(3));
+        });
+
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2, "{out}");
+        assert!(strip_ansi_codes(lines[0]).ends_with("message "));
+        assert_eq!(console::measure_text_width(lines[1]), 60);
+        assert!(
+            strip_ansi_codes(lines[1])
+                .trim_start()
+                .starts_with("disko_lib")
+        );
+    }
+
+    #[test]
+    fn elide_keeps_location() {
+        let out = capture_with(
+            TracingFormatter {
+                overflow: super::Overflow::Elide,
+                ..formatter(|_| Some(100))
+            },
+            long_scope
+        );
+
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1, "{out}");
+        assert_eq!(console::measure_text_width(lines[0]), 100);
+        let plain = strip_ansi_codes(lines[0]);
+        assert!(plain.contains("formatting disko_lib"), "{plain}");
+        assert!(plain.contains('…'), "{plain}");
+        assert!(ends_with_location(&plain), "{plain}");
+    }
+
+    #[test]
+    fn wrap_keeps_scope_aligned() {
+        let out = capture_with(
+            TracingFormatter {
+                overflow: super::Overflow::Wrap,
+                ..formatter(|_| Some(140))
+            },
+            || {
+                let _span = tracing::info_span!("mount").entered();
+                tracing::info!("{}", "word ".repeat(20));
+            }
+        );
+
+        let lines = out.lines().map(strip_ansi_codes).collect::<Vec<_>>();
+        assert!(lines.len() > 1, "{out}");
+        assert_eq!(console::measure_text_width(&lines[0]), 140);
+        assert!(lines[0].contains(" INFO word"), "{}", lines[0]);
+        assert!(ends_with_location(&lines[0]), "{}", lines[0]);
+        // Continuation is indented past time and level
+        let indent = lines[0].find(" INFO").unwrap() + " INFO ".len();
+        assert!(lines[1].starts_with(&" ".repeat(indent)), "{:?}", lines[1]);
+        assert!(lines[1].trim_start().starts_with("word"), "{:?}", lines[1]);
+    }
+
+    /// Whether plain `line` ends with source location of
+    /// this file
+    fn ends_with_location(line: &str) -> bool {
+        line.trim_end()
+            .rsplit_once("tracing.rs:")
+            .is_some_and(|(_, line)| line.parse::<u32>().is_ok())
+    }
+
+    /// Log an event inside a span with a long field
+    fn long_scope() {
+        let device = "/dev/disk/by-id/nvme-Samsung_SSD_980_PRO_2TB_S6B0NL0W123456-part1";
+        let _span = tracing::info_span!("format", device).entered();
+        tracing::info!("formatting");
+    }
+
+    #[test]
+    fn max_width_caps_terminal_width() {
+        let out = capture_with(
+            TracingFormatter {
+                max_width: Some(60),
+                ..formatter(|_| Some(120))
+            },
+            long_scope
+        );
+        let line = out.lines().next().unwrap();
+        assert_eq!(console::measure_text_width(line), 60);
+        assert!(strip_ansi_codes(line).ends_with('…'));
+
+        // Terminal is narrower than the cap
+        let out = capture_with(
+            TracingFormatter {
+                max_width: Some(100),
+                ..formatter(|_| Some(50))
+            },
+            || tracing::info!("hi")
+        );
+        assert_eq!(console::measure_text_width(out.lines().next().unwrap()), 50);
+    }
+
+    #[test]
+    fn tiny_max_width() {
+        for max_width in [0, 1] {
+            let out = capture_with(
+                TracingFormatter {
+                    max_width: Some(max_width),
+                    ..formatter(|_| Some(80))
+                },
+                long_scope
+            );
+            let plain = strip_ansi_codes(&out);
+            assert!(plain.contains("INFO formatting"), "{plain}");
+            assert_eq!(plain.lines().nth(1), Some("…"), "{plain}");
+        }
+    }
+
+    #[test]
+    fn max_width_without_terminal() {
+        let out = capture_with(
+            TracingFormatter {
+                max_width: Some(70),
+                ..formatter(|_| None)
+            },
+            long_scope
+        );
+        let line = out.lines().next().unwrap();
+        assert!(console::measure_text_width(line) <= 70);
+        assert!(strip_ansi_codes(line).ends_with('…'));
+
+        // Short lines stay unpadded
+        let out = capture_with(
+            TracingFormatter {
+                max_width: Some(200),
+                ..formatter(|_| None)
+            },
+            || tracing::info!("hi")
+        );
+        assert!(strip_ansi_codes(&out).contains(" hi disko_lib"));
+        assert!(console::measure_text_width(&out) < 200);
+    }
+
+    #[test]
+    fn filter_directives() {
+        let buf = MemoryWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_env_filter(super::filter("warn").unwrap())
+            .event_format(formatter(|_| None))
+            .with_writer(buf.clone())
+            .finish()
+            .with(FieldsLayer::default());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("hidden");
+            tracing::warn!("shown");
+        });
+
+        let out = buf.contents();
+        assert!(!out.contains("hidden"), "{out}");
+        assert!(out.contains("shown"), "{out}");
+
+        assert!(super::filter("disko_lib=loud").is_err());
+    }
+
+    #[test]
+    fn invalid_rust_log_is_skipped() {
+        use tracing::level_filters::LevelFilter;
+
+        let (filter, warning) = super::lossy_filter("disko_lib=loud", LevelFilter::INFO);
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::INFO));
+        assert!(warning.unwrap().contains("RUST_LOG"));
+
+        let (filter, warning) = super::lossy_filter("warn", LevelFilter::INFO);
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::WARN));
+        assert_eq!(warning, None);
+    }
+
+    #[test]
+    fn verbosity() {
+        use tracing::level_filters::LevelFilter;
+
+        use super::verbosity_level;
+
+        assert_eq!(verbosity_level(0), LevelFilter::INFO);
+        assert_eq!(verbosity_level(1), LevelFilter::DEBUG);
+        assert_eq!(verbosity_level(2), LevelFilter::TRACE);
+        assert_eq!(verbosity_level(i8::MAX), LevelFilter::TRACE);
+        assert_eq!(verbosity_level(-1), LevelFilter::WARN);
+        assert_eq!(verbosity_level(-2), LevelFilter::ERROR);
+        assert_eq!(verbosity_level(-3), LevelFilter::OFF);
+        assert_eq!(verbosity_level(i8::MIN), LevelFilter::OFF);
+    }
+
+    #[test]
+    fn file_log_is_plain() {
+        let dir = std::env::temp_dir().join(format!("disko-log-{}", std::process::id()));
+        let path = dir.join("nested").join("disko.log");
+        let console = MemoryWriter::default();
+        let (writer, worker) = super::file_writer(&path).unwrap();
+        let guard = super::LoggingGuard::with_worker(worker);
+        let subscriber = super::subscriber(
+            formatter(|_| Some(120)),
+            || super::filter("warn").unwrap(),
+            Secrets::default(),
+            None,
+            Some(console.clone()),
+            Some(writer)
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("mount", device = "/dev/sda1").entered();
+            tracing::debug!("quiet on console");
+            tracing::warn!("loud");
+        });
+        drop(guard);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(!log.contains('\x1b'), "{log:?}");
+        assert!(log.contains("DEBUG quiet on console"), "{log}");
+        assert!(log.contains("mount(device: /dev/sda1)"), "{log}");
+        assert!(log.contains(" WARN loud"), "{log}");
+        assert!(!console.plain().contains("quiet on console"));
+        assert!(console.plain().contains(" WARN loud"));
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let path = std::env::temp_dir().join(format!("disko-redact-{}.log", std::process::id()));
+        let console = MemoryWriter::default();
+        let (writer, worker) = super::file_writer(&path).unwrap();
+        let guard = super::LoggingGuard::with_worker(worker);
+        let subscriber = super::subscriber(
+            formatter(|_| None),
+            || super::filter("info").unwrap(),
+            Secrets::new(&["passphrase", "keyfile"]),
+            None,
+            Some(console.clone()),
+            Some(writer)
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!(
+                "luks",
+                name = "crypted",
+                Passphrase = "hunter2",
+                keyfile = tracing::field::Empty
+            );
+            span.record("keyfile", "s3cr3t-bytes");
+            let _span = span.entered();
+            tracing::info!(passphrase = "hunter2", slot = 1, "opened");
+        });
+        drop(guard);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        for out in [console.plain(), log] {
+            assert!(
+                out.contains("luks(name: crypted, Passphrase: ****, keyfile: ****)"),
+                "{out}"
+            );
+            assert!(out.contains("opened passphrase=**** slot=1"), "{out}");
+            assert!(!out.contains("hunter2") && !out.contains("s3cr3t"), "{out}");
+        }
+
+        let buf = MemoryWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(super::json::JsonFormatter::default())
+            .with_writer(buf.clone())
+            .finish()
+            .with(FieldsLayer::default());
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("luks", luksPassword = "hunter2").entered();
+            tracing::info!(key_file = "/root/luks.key", "opened");
+        });
+        let line: serde_json::Value = serde_json::from_str(&buf.contents()).unwrap();
+        assert_eq!(line["scope"][0]["fields"]["luksPassword"], "****");
+        assert_eq!(line["fields"]["key_file"], "****");
+    }
+
+    #[test]
+    fn progress_bars_keep_lines() {
+        use indicatif::{
+            MultiProgress,
+            ProgressBar,
+            ProgressDrawTarget
+        };
+
+        let log = |writer: MemoryWriter| {
+            let subscriber = tracing_subscriber::fmt()
+                .event_format(formatter(|_| Some(80)))
+                .with_writer(super::progress::Suspending(writer.clone()))
+                .finish()
+                .with(FieldsLayer::default());
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!("formatting");
+                tracing::warn!("slow disk");
+            });
+            writer.plain()
+        };
+        let plain = log(MemoryWriter::default());
+
+        let bars = MultiProgress::with_draw_target(ProgressDrawTarget::hidden());
+        let bar = bars.add(ProgressBar::new(10));
+        super::set_progress_bars(Some(bars));
+        let suspended = log(MemoryWriter::default());
+        super::set_progress_bars(None);
+        bar.finish();
+
+        // Timestamps may differ by a second
+        let strip = |out: &str| {
+            out.lines()
+                .map(|l| l.split_once(' ').unwrap().1.to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(strip(&plain), strip(&suspended));
+    }
+
+    #[cfg(feature = "journald")]
+    #[test]
+    fn journald_entries() {
+        use std::os::unix::net::UnixDatagram;
+
+        use super::journald::{
+            JournaldLayer,
+            field,
+            key,
+            priority
+        };
+
+        assert_eq!(priority(tracing::Level::ERROR), 3);
+        assert_eq!(priority(tracing::Level::WARN), 4);
+        assert_eq!(priority(tracing::Level::INFO), 6);
+        assert_eq!(priority(tracing::Level::TRACE), 7);
+        assert_eq!(key("_luks.key-file"), "LUKS_KEY_FILE");
+        assert_eq!(key("1st"), "FIELD_1ST");
+        assert_eq!(key("priority"), "FIELD_PRIORITY");
+        assert_eq!(key("code.file"), "FIELD_CODE_FILE");
+        assert_eq!(key("MESSAGE"), "FIELD_MESSAGE");
+
+        let mut buf = Vec::new();
+        field(&mut buf, "MESSAGE", "two\nlines");
+        assert_eq!(buf, b"MESSAGE\n\x09\0\0\0\0\0\0\0two\nlines\n");
+
+        let path = std::env::temp_dir().join(format!("disko-journal-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let journal = UnixDatagram::bind(&path).unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(FieldsLayer::default())
+            .with(JournaldLayer::with_socket(path.clone(), Secrets::default()).unwrap());
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("mount", device = "/dev/sda1", passphrase = "hunter2")
+                .entered();
+            tracing::warn!(attempt = 2, token = "abc", target = "root", "retrying");
+        });
+
+        let mut entry = vec![0; 4096];
+        let len = journal.recv(&mut entry).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let entry = String::from_utf8_lossy(&entry[..len]);
+        let lines = entry.lines().collect::<Vec<_>>();
+        for expected in [
+            "PRIORITY=4",
+            "MESSAGE=retrying",
+            "ATTEMPT=2",
+            "TARGET=disko_lib::private::tracing::tests",
+            "MOUNT_DEVICE=/dev/sda1",
+            "MOUNT_PASSPHRASE=****",
+            "TOKEN=****",
+            "FIELD_TARGET=root"
+        ] {
+            assert!(lines.contains(&expected), "{expected} in {entry}");
+        }
+        assert!(lines.iter().any(|l| l.starts_with("CODE_FILE=")), "{entry}");
+        assert!(
+            lines.iter().any(|l| l.starts_with("SYSLOG_IDENTIFIER=")),
+            "{entry}"
+        );
+    }
+
+    #[test]
+    fn unwritable_log_file() {
+        let file = std::env::temp_dir().join(format!("disko-log-file-{}", std::process::id()));
+        std::fs::write(&file, "").unwrap();
+        // Parent is a regular file
+        let err = super::file_writer(&file.join("disko.log")).unwrap_err();
+        std::fs::remove_file(&file).unwrap();
+        assert!(
+            err.to_string().contains("Failed to create log directory"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        let buf = MemoryWriter::default();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(super::json::JsonFormatter::default())
+            .with_writer(buf.clone())
+            .finish()
+            .with(FieldsLayer::default());
+        tracing::subscriber::with_default(subscriber, || {
+            let _disk = tracing::info_span!("disk", name = "main").entered();
+            let _mount = tracing::info_span!("mount", device = "/dev/sda1", ro = true).entered();
+            tracing::warn!(attempt = 2, "retrying");
+        });
+
+        let out = buf.contents();
+        assert_eq!(out.lines().count(), 1, "{out}");
+        let line: serde_json::Value = serde_json::from_str(&out).unwrap();
+        assert!(line["timestamp"].as_u64().unwrap() > 0);
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["target"], "disko_lib::private::tracing::tests");
+        assert_eq!(line["message"], "retrying");
+        assert_eq!(line["fields"], serde_json::json!({ "attempt": 2 }));
+        assert!(line["file"].as_str().unwrap().ends_with("tracing.rs"));
+        assert!(line["line"].is_u64());
+        assert_eq!(
+            line["scope"],
+            serde_json::json!([
+                { "name": "disk", "fields": { "name": "main" } },
+                { "name": "mount", "fields": { "device": "/dev/sda1", "ro": true } }
+            ])
+        );
+    }
+
+    #[test]
+    fn install_twice() {
+        drop(super::install_with_filter("off").unwrap());
+        drop(super::install().unwrap());
+
+        let dir = std::env::temp_dir().join(format!("disko-twice-{}", std::process::id()));
+        let guard = super::install_with_file(&dir.join("disko.log")).unwrap();
+        assert!(guard.workers.is_empty());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn disabled_spans_stay_cheap() {
+        use std::fmt::{
+            Debug,
+            Formatter
+        };
+        use std::sync::atomic::{
+            AtomicUsize,
+            Ordering
+        };
+
+        use tracing::Level;
+
+        /// Counts how often it was formatted
+        struct Probe<'a>(&'a AtomicUsize);
+
+        impl Debug for Probe<'_> {
+            fn fmt(
+                &self,
+                f: &mut Formatter<'_>
+            ) -> std::fmt::Result {
+                self.0.fetch_add(1, Ordering::Relaxed);
+                f.write_str("probe")
+            }
+        }
+
+        let formatted = AtomicUsize::new(0);
+        let subscriber = super::subscriber(
+            formatter(|_| None),
+            || super::filter("warn").unwrap(),
+            Secrets::default(),
+            None,
+            Some(MemoryWriter::default()),
+            None
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(Level::TRACE));
+            assert!(tracing::enabled!(Level::WARN));
+            let _span = tracing::trace_span!("probe", value = ?Probe(&formatted)).entered();
+        });
+        assert_eq!(formatted.load(Ordering::Relaxed), 0);
+
+        // File gets everything
+        let path = std::env::temp_dir().join(format!("disko-cheap-{}.log", std::process::id()));
+        let (writer, worker) = super::file_writer(&path).unwrap();
+        let subscriber = super::subscriber(
+            formatter(|_| None),
+            || super::filter("warn").unwrap(),
+            Secrets::default(),
+            None,
+            Some(MemoryWriter::default()),
+            Some(writer)
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(tracing::enabled!(Level::TRACE));
+        });
+        drop(worker);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn custom_writer_is_piped() {
+        let buf = MemoryWriter::default();
+        let log = super::build(super::LogOptions {
+            filter: Some("info".into()),
+            writer: Some(tracing_subscriber::fmt::writer::BoxMakeWriter::new(
+                buf.clone()
+            )),
+            ..super::LogOptions::default()
+        })
+        .unwrap();
+        let dispatch = log.dispatch.unwrap();
+        tracing::dispatcher::with_default(&dispatch, || tracing::info!("captured"));
+
+        // Same whether tests run on a terminal or not
+        assert!(!buf.contents().contains('\x1b'), "{:?}", buf.contents());
+        assert!(buf.contents().contains(" INFO captured"));
+    }
+
+    #[test]
+    fn memory_writer_gets_real_output() {
+        let buf = MemoryWriter::default();
+        let subscriber = super::subscriber(
+            formatter(|_| None),
+            || super::filter("info").unwrap(),
+            Secrets::default(),
+            None,
+            Some(buf.clone()),
+            None
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("mount", device = "/dev/sda1").entered();
+            tracing::info!("mounted");
+            tracing::debug!("filtered out");
+        });
+
+        assert!(buf.contents().contains('\x1b'));
+        let plain = buf.plain();
+        assert_eq!(plain.lines().count(), 1, "{plain}");
+        assert!(plain.contains(" INFO mounted disko_lib"), "{plain}");
+        assert!(plain.contains("mount(device: /dev/sda1)"), "{plain}");
+    }
+
+    #[test]
+    fn no_color_is_plain() {
+        let out = capture_with(
+            TracingFormatter {
+                colorize: |_| false,
+                ..formatter(|_| Some(80))
+            },
+            long_scope
+        );
+        assert!(!out.contains('\x1b'), "{out:?}");
+        assert_eq!(out.lines().next().unwrap().chars().count(), 80);
+    }
 }